@@ -1,17 +1,29 @@
 use {
-    crate::{ElementExt, Event, Opt, Race, Scraper, Year},
-    anyhow::{anyhow, bail, Result as AResult},
+    crate::{
+        paginator::{NextPage, Paginator},
+        progress,
+        retry::Retry,
+        row::texts,
+        times::Times,
+        ClientExt, ElementExt, Event, Opt, Race, Scraper, Year,
+    },
+    anyhow::{anyhow, bail, Context, Result as AResult},
     async_trait::async_trait,
     digital_duration_nom::duration::Duration,
     fantoccini::{
         elements::Element,
+        error::CmdError,
         Client,
         Locator::{Css, XPath},
     },
-    futures::stream::{self, StreamExt},
+    futures::{
+        stream::{self, StreamExt, TryStreamExt},
+        Future,
+    },
     serde::Serialize,
     serde_json::value,
     std::num::{NonZeroU16, NonZeroU8},
+    tracing::{instrument, warn},
 };
 
 const DATE_DIV: &str = "#eventDate";
@@ -21,253 +33,151 @@ pub struct Params {
     event_id: u32,
     second_id: u32,
     race: Race,
-    year: Year,
-}
-
-#[derive(Copy, Clone)]
-enum Match {
-    Contains,
-    Exact,
+    // None for an event given by its results URL, which is already the
+    // year to scrape.
+    year: Option<Year>,
+    paginator: Paginator,
+    retry: Retry,
 }
 
 impl Params {
+    // The race isn't checked here; any race the event's popover offers can
+    // be chosen, and select_race reports the available labels otherwise.
     pub(crate) fn new(opt: Opt) -> AResult<Self> {
         use Event::*;
 
-        match opt.event {
-            Rftz => Self::new_rtfz(opt),
-            Lt100 => Self::new_lt100(opt),
-            DukeCityMarathon => Self::new_dcm(opt),
-            CorralesDitchRun => Self::new_cdr(opt),
-            KotH => Self::new_koth(opt),
-            RioGrande => Self::new_rio_grande(opt),
-            DoggieDash => Self::new_doggie_dash(opt),
-            _ => bail!("{:?} is not athlinks", opt.event),
-        }
-    }
-
-    fn new_rtfz(opt: Opt) -> AResult<Self> {
-        use Race::*;
-
-        match opt.race {
-            TenK | Half | FiveK => {}
-            _ => bail!("Only Half, 10k and 5k are available"),
-        };
-
-        Ok(Self {
-            event_id: 34346,
-            second_id: 729962,
-            race: opt.race,
-            year: opt.year,
-        })
-    }
-
-    fn new_lt100(opt: Opt) -> AResult<Self> {
-        use Race::*;
-
-        if let Full = opt.race {
-        } else {
-            bail!("Only the full is available");
-        }
-
-        Ok(Self {
-            event_id: 33913,
-            second_id: 1064640,
-            race: opt.race,
-            year: opt.year,
-        })
-    }
-
-    fn new_dcm(opt: Opt) -> AResult<Self> {
-        use Race::*;
-
-        if opt.year == "2022".parse().unwrap() {
-            match opt.race {
-                Full | Half | FiveK | TenK => {}
-                _ => bail!("Only Full, Half, 10k and 5k are available"),
+        let (event_id, second_id) = match opt.event {
+            Rftz => (34346, 729962),
+            Lt100 => (33913, 1064640),
+            DukeCityMarathon => {
+                if !(2022..=2024).contains(&opt.year().0) {
+                    bail!("Only 2022, 2023 or 2024 (for now)");
+                }
+                (35398, 1032202)
             }
-        } else if opt.year == "2023".parse().unwrap() {
-            match opt.race {
-                Full | Half | FiveK | TenK => {}
-                _ => bail!("Only Full, Half, 10k and 5k are available"),
+            CorralesDitchRun => {
+                /*
+                2008 All, 10k, 5k
+                2011 All, 5k, 10k
+                2012 All, 10k
+                2015 All, 10k, 5k
+                2017 All, Half, 10k, 5k
+                2018 All, Half, 10k, 5k, Half-3-person-relay
+                2019 All, Half, 10k, 5k, Half-3-person-relay
+                2021 All, Half, Kids, Virtual-Half, Virtual 10k, Virtual 5k, 5k, 10k
+                2022 All, Kids, 10k, Half, 5k
+                2023 All, Half, 10k, 5k, SAR-Technical-Team, Kids
+                 */
+
+                if opt.year().0 != 2023 {
+                    bail!("Only 2023 (for now?)");
+                }
+                (6398, 1079194)
             }
-        } else if opt.year == "2024".parse().unwrap() {
-            match opt.race {
-                Full | Half | FiveK | TenK => {}
-                _ => bail!("Only Full, Half, 10k and 5k are available"),
+            KotH => (166931, 1064112),
+            RioGrande => (11260, 1040305),
+            DoggieDash => (68104, 1094706),
+            Athlinks => {
+                let id: String = opt.id()?;
+                match id.split_once('/').map(|(e, s)| (e.parse(), s.parse())) {
+                    Some((Ok(event_id), Ok(second_id))) => (event_id, second_id),
+                    _ => bail!("{id:?} isn't the two ids in a results URL, e.g. 34346/729962"),
+                }
             }
-        } else {
-            bail!("Only 2022, 2023 or 2024 (for now)");
-        };
-
-        Ok(Self {
-            event_id: 35398,
-            second_id: 1032202,
-            race: opt.race,
-            year: opt.year,
-        })
-    }
-
-    fn new_cdr(opt: Opt) -> AResult<Self> {
-        use Race::*;
-
-        /*
-        2008 All, 10k, 5k
-        2011 All, 5k, 10k
-        2012 All, 10k
-        2015 All, 10k, 5k
-        2017 All, Half, 10k, 5k
-        2018 All, Half, 10k, 5k, Half-3-person-relay
-        2019 All, Half, 10k, 5k, Half-3-person-relay
-        2021 All, Half, Kids, Virtual-Half, Virtual 10k, Virtual 5k, 5k, 10k
-        2022 All, Kids, 10k, Half, 5k
-        2023 All, Half, 10k, 5k, SAR-Technical-Team, Kids
-         */
-
-        if opt.year != "2023".parse().unwrap() {
-            bail!("Only 2023 (for now?)");
-        }
-
-        match opt.race {
-            TenK | Half | FiveK => {}
-            _ => bail!("Only Half, 10k and 5k are available"),
+            _ => bail!("{:?} is not athlinks", opt.event),
         };
-
-        Ok(Self {
-            event_id: 6398,
-            second_id: 1079194,
-            race: opt.race,
-            year: opt.year,
-        })
-    }
-
-    fn new_koth(opt: Opt) -> AResult<Self> {
-        use Race::*;
-
-        match opt.race {
-            FiveK | TenK | TenKRuck | Half => {}
-            _ => bail!("Only 5k, 10k, 10kruck and half are available"),
+        let year = match opt.event {
+            Athlinks => opt.year,
+            _ => Some(opt.year()),
         };
 
         Ok(Self {
-            event_id: 166931,
-            second_id: 1064112,
-            race: opt.race,
-            year: opt.year,
-        })
-    }
-
-    fn new_rio_grande(opt: Opt) -> AResult<Self> {
-        use Race::*;
-
-        match opt.race {
-            FiveK | Half => {}
-            _ => bail!("Only 5k and half are available"),
-        };
-
-        Ok(Self {
-            event_id: 11260,
-            second_id: 1040305,
-            race: opt.race,
-            year: opt.year,
-        })
-    }
-
-    fn new_doggie_dash(opt: Opt) -> AResult<Self> {
-        use Race::*;
-
-        match opt.race {
-            FiveK => {}
-            _ => bail!("Only 5k and half are available"),
-        };
-        Ok(Self {
-            event_id: 68104,
-            second_id: 1094706,
-            race: opt.race,
-            year: opt.year,
+            event_id,
+            second_id,
+            paginator: Paginator::new(&opt, NEXT_PAGE, ROWS_CSS),
+            retry: Retry::new(&opt),
+            race: opt.race.unwrap_or(Race::Full),
+            year,
         })
     }
 
+    #[instrument(skip_all)]
     async fn accept_cookies(&self, c: &Client) -> AResult<()> {
-        c.wait()
-            .for_element(XPath("//button[text()='okay, got it']"))
+        c.wait_for(XPath("//button[text()='okay, got it']"))
             .await?
             .click()
             .await
             .map_err(Into::into)
     }
 
+    #[instrument(skip_all)]
     async fn click_date_to_bring_up_event_filter(&self, c: &Client) -> AResult<()> {
         const DATE_BUTTON: &str = "div.MuiChip-clickable";
 
-        let e = c.wait().for_element(Css(DATE_BUTTON)).await.map_err(|e| {
-            let message = format!("Couldn't find {}: {e:?}", DATE_BUTTON);
-            eprintln!("{}", message);
-            anyhow!(message)
-        })?;
+        let e = c
+            .wait_for(Css(DATE_BUTTON))
+            .await
+            .with_context(|| format!("Couldn't find {DATE_BUTTON}"))?;
         e.click().await?;
-        c.wait().for_element(Css(DATE_DIV)).await.map_err(|e| {
-            let message = format!("Couldn't find {}: {e:?}", DATE_DIV);
-            eprintln!("{}", message);
-            anyhow!(message)
-        })?;
+        c.wait_for(Css(DATE_DIV))
+            .await
+            .with_context(|| format!("Couldn't find {DATE_DIV}"))?;
         Ok(())
     }
 
+    #[instrument(skip_all, fields(year = ?self.year))]
     async fn select_year(&self, c: &Client) -> AResult<()> {
-        let year = self.year.to_string();
-        pop_up_select(c, DATE_DIV, &[&year[..]], Match::Contains).await
+        let year = match self.year {
+            None => return Ok(()),
+            Some(year) => year.to_string(),
+        };
+        // The labels are dates, which all look alike to fuzzy matching, and
+        // the first in the year is the one that's meant.
+        pop_up_select(c, DATE_DIV, |labels| {
+            labels
+                .iter()
+                .position(|label| label.contains(&year))
+                .ok_or_else(|| anyhow!("No {year} among {labels:?}"))
+        })
+        .await
     }
 
+    #[instrument(skip_all, fields(race = %self.race))]
     async fn select_race(&self, c: &Client) -> AResult<()> {
-        pop_up_select(c, RACE_DIV, self.race.li_text(), Match::Exact).await
+        pop_up_select(c, RACE_DIV, |labels| {
+            self.race.choose(labels, self.race.li_text())
+        })
+        .await
     }
 }
 
+#[instrument(skip_all)]
 async fn click_apply_filter(c: &Client) -> AResult<()> {
-    c.wait()
-        .for_element(XPath("//span[text()='Apply Filter']"))
+    c.wait_for(XPath("//span[text()='Apply Filter']"))
         .await?
         .really_click(c)
         .await
 }
 
 const BUTTON_CSS: &str = "#pager>div>div>button";
+const ROWS_CSS: &str = ".row.mx-0.link-to-irp";
+const NEXT_PAGE: NextPage = NextPage::LastWithHtml {
+    css: BUTTON_CSS,
+    html: "&gt;",
+};
 
-async fn print_placements(c: &Client) -> AResult<()> {
-    c.wait().for_element(Css(BUTTON_CSS)).await?;
-    let placements = stream::iter(
-        c.find_all(Css(".row.mx-0.link-to-irp"))
-            .await?
-            .into_iter()
-            .take(50),
-    )
-    .filter_map(Placement::from_element)
-    .collect::<Vec<_>>()
-    .await;
-    println!("{}", serde_json::to_string(&placements).unwrap());
-    Ok(())
-}
-
-async fn next_button(c: &Client) -> AResult<Option<Element>> {
-    let buttons = c.find_all(Css(BUTTON_CSS)).await?;
-    let e = buttons.last().unwrap();
-    let done = e.html(true).await? != "&gt;";
-    Ok(if done { None } else { Some(e.clone()) })
-}
-
-async fn extract_placements(c: &Client) -> AResult<()> {
-    let mut button;
-
-    while {
-        print_placements(c).await?;
-        button = next_button(c).await?;
-        button.is_some()
-    } {
-        button.unwrap().click().await?;
-        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-    }
-    Ok(())
+// The rows of the page, as individuals or as teams, depending on
+// from_element.
+async fn page<T, Fut>(c: &Client, from_element: impl FnMut(Element) -> Fut) -> AResult<Vec<T>>
+where
+    Fut: Future<Output = Option<T>>,
+{
+    c.wait_for(Css(BUTTON_CSS)).await?;
+    let rows = c.find_all(Css(ROWS_CSS)).await?;
+    Ok(stream::iter(rows.into_iter().take(50))
+        .filter_map(from_element)
+        .collect()
+        .await)
 }
 
 #[derive(Serialize)]
@@ -281,7 +191,8 @@ struct Placement {
     gender_rank: Option<NonZeroU16>,
     division_rank: NonZeroU16,
     pace: Duration,
-    time: Duration,
+    #[serde(flatten)]
+    times: Times,
 }
 
 macro_rules! element_text {
@@ -347,7 +258,9 @@ impl Placement {
             let gender_rank = parsed_elements_text!(es).ok();
             let division_rank = parsed_elements_text!(es)?;
             let pace = elements_text!(es)?.split('\n').next().unwrap().parse()?;
-            let time = parsed_elements_text!(es)?;
+            // Athlinks' list shows whichever time the event ranks by
+            // without saying which one it is.
+            let times = Times::unknown(Some(parsed_elements_text!(es)?));
             Ok(Placement {
                 name,
                 sex,
@@ -358,26 +271,148 @@ impl Placement {
                 gender_rank,
                 division_rank,
                 pace,
-                time,
+                times,
             })
         }
         let result = from_element(&e).await;
-        if let Err(err) = &result {
-            // If this line is being discarded, we want to dump enough
-            // info to figure out why.  We know we're going to ignore
-            // column headings and DNFs.
-            let text = e.text().await;
-            if let Ok(text) = text.as_ref() {
-                if let Some(last) = text.split('\n').next_back() {
-                    if last == "DNF" || last == "TIME" {
-                        return None;
+        unless_discarded(&e, result).await
+    }
+}
+
+/// A relay team's row.  Athlinks' results list only has the team as a
+/// whole; the members are only on the team's individual results page,
+/// which the row links to.
+#[derive(Serialize)]
+struct TeamPlacement {
+    team_name: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    members: Vec<String>,
+    #[serde(skip)]
+    irp: Option<String>,
+    bib: String,
+    hometown: String,
+    rank: NonZeroU16,
+    gender_rank: Option<NonZeroU16>,
+    division_rank: NonZeroU16,
+    pace: Duration,
+    #[serde(flatten)]
+    times: Times,
+}
+
+impl TeamPlacement {
+    async fn from_element(e: Element) -> Option<Self> {
+        async fn from_element(e: &Element) -> AResult<TeamPlacement> {
+            let team_name = element_text!(e, ".athName")?;
+
+            // Teams have no sex or age, so this is just the bib and
+            // hometown, either of which may be missing.
+            let (bib, hometown) = {
+                let text = element_text!(e, ".col-12")?;
+                let mut bib = String::new();
+                let mut hometown = String::new();
+                for piece in text.split('\n') {
+                    match piece.strip_prefix("Bib ") {
+                        Some(b) => bib = b.to_string(),
+                        None => hometown = piece.to_string(),
                     }
                 }
+                (bib, hometown)
+            };
+            let mut es = e.find_all(Css(".px-0")).await?.into_iter();
+            let rank = parsed_elements_text!(es)?;
+            let gender_rank = parsed_elements_text!(es).ok();
+            let division_rank = parsed_elements_text!(es)?;
+            let pace = elements_text!(es)?.split('\n').next().unwrap().parse()?;
+            let times = Times::unknown(Some(parsed_elements_text!(es)?));
+            // The row is either the link itself or has one in it.
+            let irp = match e.prop("href").await? {
+                Some(href) => Some(href),
+                None => match e.find(Css("a[href]")).await {
+                    Ok(a) => a.prop("href").await?,
+                    Err(_) => None,
+                },
+            };
+            Ok(TeamPlacement {
+                team_name,
+                members: Vec::new(),
+                irp,
+                bib,
+                hometown,
+                rank,
+                gender_rank,
+                division_rank,
+                pace,
+                times,
+            })
+        }
+        let result = from_element(&e).await;
+        unless_discarded(&e, result).await
+    }
+}
+
+// Each member's name on a team's individual results page links to the
+// member's athlete page.
+const MEMBER_CSS: &str = "a[href*='/athletes/']";
+
+// The teams on the page, each with the members from its individual results
+// page.
+async fn team_page(c: &Client) -> AResult<Vec<TeamPlacement>> {
+    let mut teams = page(c, TeamPlacement::from_element).await?;
+    for team in &mut teams {
+        match &team.irp {
+            Some(irp) => team.members = members(c, irp).await?,
+            None => warn!(team = %team.team_name, "no link to the team's members"),
+        }
+    }
+    Ok(teams)
+}
+
+// The individual results page is read in a tab of its own, so that the
+// results list (and its page number) stays where it is.
+#[instrument(skip(c))]
+async fn members(c: &Client, irp: &str) -> AResult<Vec<String>> {
+    let list = c.window().await?;
+    let tab = c.new_window(true).await?.handle;
+    c.switch_to_window(tab).await?;
+    let members = async {
+        c.goto(irp).await?;
+        // Not every team's page links its members.
+        match c.wait_for(Css(MEMBER_CSS)).await {
+            Err(CmdError::WaitTimeout) => {
+                warn!(irp, "no members on the team's page");
+                return Ok(Vec::new());
+            }
+            result => result?,
+        };
+        let mut members = texts(c.find_all(Css(MEMBER_CSS)).await?).await?;
+        // A member's name can be linked more than once, e.g. from a photo.
+        members.retain(|member| !member.trim().is_empty());
+        members.dedup();
+        Ok::<_, anyhow::Error>(members)
+    }
+    .await;
+    c.close_window().await?;
+    c.switch_to_window(list).await?;
+    members
+}
+
+async fn unless_discarded<T>(e: &Element, result: AResult<T>) -> Option<T> {
+    if let Err(err) = &result {
+        // If this line is being discarded, we want to dump enough
+        // info to figure out why.  We know we're going to ignore
+        // column headings and DNFs.
+        let text = e.text().await;
+        if let Ok(text) = text.as_ref() {
+            if let Some(last) = text.split('\n').next_back() {
+                if last == "DNF" || last == "TIME" {
+                    return None;
+                }
             }
-            eprintln!("discarding {text:?}, {err:?}");
         }
-        result.ok()
+        progress::discarded();
+        warn!(?text, error = ?err, "discarding row");
     }
+    result.ok()
 }
 
 // This should choose Event Date and Race. Division and Split will default
@@ -385,12 +420,15 @@ impl Placement {
 // I've verified this with each of the Athlinks events we currently scrape.
 // filter options.
 
-async fn pop_up_select(c: &Client, selector: &str, containing: &[&str], m: Match) -> AResult<()> {
-    let e = c.wait().for_element(Css(selector)).await.map_err(|e| {
-        let message = format!("Couldn't find {selector}: {e:?}");
-        eprintln!("{}", message);
-        anyhow!(message)
-    })?;
+async fn pop_up_select(
+    c: &Client,
+    selector: &str,
+    choose: impl FnOnce(&[String]) -> AResult<usize>,
+) -> AResult<()> {
+    let e = c
+        .wait_for(Css(selector))
+        .await
+        .with_context(|| format!("Couldn't find {selector}"))?;
 
     if let Some(class) = e.attr("class").await? {
         if class.contains("Mui-disabled") {
@@ -406,47 +444,17 @@ async fn pop_up_select(c: &Client, selector: &str, containing: &[&str], m: Match
     e.obscured_click(c).await?;
 
     let e = c
-        .wait()
-        .for_element(Css("div.MuiPopover-paper ul"))
+        .wait_for(Css("div.MuiPopover-paper ul"))
         .await
-        .map_err(|e| {
-            let message = format!("Couldn't find popover {selector}: {e:?}");
-            eprintln!("{}", message);
-            anyhow!(message)
-        })?;
-
-    let mut stream = stream::iter(e.find_all(Css("li")).await?);
-    let mut found = None;
-    while {
-        let e;
-        (e, stream) = stream.into_future().await;
-        match e {
-            None => false,
-            Some(e) => match e.text().await {
-                Err(e) => {
-                    eprintln!("trouble with stream: {e:?}");
-                    false
-                }
-                Ok(t) => {
-                    // NOTE: contains won't work for race.  It's fine for year,
-                    // but "Half Marathon" contains "Marathon"
-                    if containing.iter().any(|c| match m {
-                        Match::Contains => t.contains(c),
-                        Match::Exact => t == *c,
-                    }) {
-                        found = Some(e);
-                        false
-                    } else {
-                        true
-                    }
-                }
-            },
-        }
-    } {}
-    match found {
-        None => bail!("Could not find {selector} {:?}", containing),
-        Some(e) => e.click().await?,
-    }
+        .with_context(|| format!("Couldn't find popover {selector}"))?;
+
+    let mut items = e.find_all(Css("li")).await?;
+    let labels = stream::iter(&items)
+        .then(|e| e.text())
+        .try_collect::<Vec<_>>()
+        .await?;
+    let index = choose(&labels).map_err(|e| anyhow!("{selector}: {e}"))?;
+    items.swap_remove(index).click().await?;
     Ok(())
 }
 
@@ -460,16 +468,38 @@ impl Scraper for Params {
     }
 
     async fn doit(&self, client: &Client) -> AResult<()> {
-        self.accept_cookies(client).await?;
-        self.click_date_to_bring_up_event_filter(client).await?;
-        self.select_year(client).await?;
-        self.select_race(client).await?;
-        click_apply_filter(client).await?;
-        extract_placements(client).await
+        let retry = self.retry;
+        retry.run(client, || self.accept_cookies(client)).await?;
+        // Each of the filter's steps depends on the ones before it, so when
+        // one fails, they're all done again on the page freshly loaded.
+        let mut reload = false;
+        retry
+            .run(client, || {
+                let reload = std::mem::replace(&mut reload, true);
+                async move {
+                    if reload {
+                        client.goto(&self.url()).await?;
+                    }
+                    self.click_date_to_bring_up_event_filter(client).await?;
+                    self.select_year(client).await?;
+                    self.select_race(client).await?;
+                    click_apply_filter(client).await
+                }
+            })
+            .await?;
+        if self.race.is_relay() {
+            self.paginator.paginate(client, || team_page(client)).await
+        } else {
+            self.paginator
+                .paginate(client, || page(client, Placement::from_element))
+                .await
+        }
     }
 }
 
 trait RaceExt {
+    /// The labels this race has in the race popover that fuzzy matching
+    /// can't find, because they don't say what the race is.
     fn li_text(&self) -> &'static [&'static str];
 }
 
@@ -477,25 +507,10 @@ impl RaceExt for Race {
     fn li_text(&self) -> &'static [&'static str] {
         use Race::*;
         match self {
-            Full => &["Marathon"],
-            Half => &["Half Marathon", "Lovelace Rio Grande Half Marathon"],
-            Relay => unreachable!(),
-            TenK => &["10K Timed", "10K Run", "10k"],
-            FiveK => &[
-                "5K Timed",
-                "5K Run",
-                "5K Run/Walk",
-                "5k",
-                "Garrity Group 5k Run/Walk",
-                "One Medal 5k Run/Walk",
-                "Doggie Dash",
-            ],
-            Handcycle => unreachable!(),
+            FiveK => &["Doggie Dash"],
+            // "Rucksack" isn't "ruck" as far as fuzzy matching goes.
             TenKRuck => &["10k Rucksack"],
-            SoloMaleCivilianHeavy
-            | SoloMaleMilitaryHeavy
-            | SoloMaleROTCHeavy
-            | SoloFemaleMilitaryHeavy => unreachable!(),
+            _ => &[],
         }
     }
 }
@@ -1,44 +1,54 @@
 use {
-    crate::{take_until_and_consume, ElementExt, Opt, Race, Scraper, Year},
+    crate::{
+        paginator::{NextPage, Paginator},
+        placement::Split,
+        retry::Retry,
+        take_until_and_consume,
+        times::Times,
+        ClientExt, ElementExt, Event, Opt, Race, Scraper, Year,
+    },
     anyhow::{anyhow, bail, Result as AResult},
     async_trait::async_trait,
     digital_duration_nom::duration::Duration,
-    fantoccini::{elements::Element, Client, Locator::Css},
+    fantoccini::{Client, Locator::Css},
     nom::{
         bytes::complete::{tag, take_until},
         character::complete::{multispace0, multispace1},
         combinator::{all_consuming, map, map_res, opt, value},
-        error::Error,
         multi::many1,
-        sequence::{preceded, terminated},
+        sequence::{delimited, preceded, terminated},
         IResult, Parser,
     },
     serde::Serialize,
-    std::{collections::HashMap, num::NonZeroU16, str::FromStr},
+    std::{num::NonZeroU16, str::FromStr},
+    tracing::instrument,
 };
 
+#[instrument(skip_all)]
 async fn click_the_results_tab(c: &Client) -> AResult<()> {
-    c.wait()
-        .for_element(Css("#resultsResultsTab"))
+    c.wait_for(Css("#resultsResultsTab"))
         .await?
         .really_click(c)
         .await
 }
 
-async fn choose_the_race(c: &Client, menu_item: &'static str) -> AResult<()> {
+#[instrument(skip(c))]
+async fn choose_the_race(c: &Client, race: &Race) -> AResult<()> {
     let element = c.find(Css("#bazu-full-results-races")).await?;
     let html = element.html(true).await?;
-    match value_map_from_options(&html)?.get(menu_item) {
-        None => bail!("Could not find menu item {}", menu_item),
-        Some((value, selected)) => {
-            if !selected {
-                element.select_by_value(value).await?
-            };
-            Ok(())
-        }
-    }
+    let options = options(&html)?;
+    let menu_items = options
+        .iter()
+        .map(|&(_, _, menu_item)| menu_item)
+        .collect::<Vec<_>>();
+    let (value, selected, _) = options[race.choose(&menu_items, &[])?];
+    if !selected {
+        element.select_by_value(value).await?
+    };
+    Ok(())
 }
 
+#[instrument(skip_all)]
 async fn choose_100_per_page(c: &Client) -> AResult<()> {
     Ok(c.find(Css("#bazu-full-results-paging"))
         .await?
@@ -46,38 +56,15 @@ async fn choose_100_per_page(c: &Client) -> AResult<()> {
         .await?)
 }
 
-async fn print_placements(c: &Client) -> AResult<()> {
-    let text = c.source().await?;
-    if let Ok((_, placements)) = placements(&text) {
-        println!("{}", serde_json::to_string(&placements).unwrap());
-    }
-    Ok(())
-}
-
-async fn next_button(c: &Client) -> AResult<Option<Element>> {
-    let element = c.find(Css("#bazu-full-results-grid_next")).await?;
-    Ok(if let Some(classes) = element.attr("class").await? {
-        if classes.contains("ui-state-disabled") {
-            None
-        } else {
-            Some(element)
-        }
-    } else {
-        None
-    })
-}
+// The next button's class includes "ui-state-disabled" on the last page.
+const NEXT_PAGE: NextPage = NextPage::UnlessDisabled("#bazu-full-results-grid_next");
 
-async fn extract_placements(c: &Client) -> AResult<()> {
-    let mut button;
-
-    while {
-        print_placements(c).await?;
-        button = next_button(c).await?;
-        button.is_some()
-    } {
-        button.unwrap().click().await?;
-    }
-    Ok(())
+// A page that doesn't parse has nothing on it to print.
+async fn page<T>(c: &Client, parse: fn(&str) -> IResult<&str, Vec<T>>) -> AResult<Vec<T>> {
+    let text = c.source().await?;
+    Ok(parse(&text)
+        .map(|(_, placements)| placements)
+        .unwrap_or_default())
 }
 
 #[derive(Serialize)]
@@ -85,7 +72,8 @@ struct Placement {
     rank: NonZeroU16,
     name: String,
     bib: String,
-    time: Duration,
+    #[serde(flatten)]
+    times: Times,
     pace: Duration,
     hometown: String,
     age: Option<u8>,
@@ -95,121 +83,196 @@ struct Placement {
 }
 
 impl Placement {
-    // new takes its arguments as a tuple so that it has a single argument and
-    // hence can be used as the second argument to map.
-    #[allow(clippy::type_complexity)]
-    fn new<'a>(
-        (rank, name, bib, time, pace, hometown, age, sex, division, division_rank): (
-            NonZeroU16,
-            &'a str,
-            &'a str,
-            Duration,
-            Duration,
-            &'a str,
-            Option<u8>,
-            Option<&'a str>,
-            &'a str,
-            NonZeroU16,
-        ),
-    ) -> Self {
-        let name = name.to_string();
-        let bib = bib.to_string();
-        let hometown = hometown.to_string();
-        let sex = sex.map(|sex| sex.to_string());
-        let division = division.to_string();
-        Self {
-            rank,
-            name,
-            bib,
-            time,
-            pace,
-            hometown,
-            age,
-            sex,
-            division,
-            division_rank,
-        }
+    fn from_cells(cells: Cells) -> AResult<Self> {
+        // Some events have separate chip and gun time columns in addition
+        // to the time they rank by.
+        let times = Times::new(
+            cells.optional("time"),
+            cells.optional("chiptime"),
+            cells.optional("guntime"),
+        )
+        .ok_or_else(|| anyhow!("no time in {cells:?}"))?;
+
+        Ok(Self {
+            rank: cells.parsed("rank")?,
+            name: cells.text("name")?.to_string(),
+            bib: cells.text("bib")?.to_string(),
+            times,
+            pace: cells.parsed("pace")?,
+            hometown: cells.text("hometown")?.to_string(),
+            age: cells.optional("age"),
+            sex: cells.get("sex").map(ToString::to_string),
+            division: cells.text("agroup")?.to_string(),
+            division_rank: cells.parsed("agrank")?,
+        })
+    }
+}
+
+/// A relay team's row.  The relay grid has the team's name where the
+/// individual grid has the runner's, and, on events that report them, a
+/// column of members and a column per leg, whose times are splits like
+/// any other site's.
+#[derive(Serialize)]
+struct TeamPlacement {
+    rank: NonZeroU16,
+    team_name: String,
+    bib: String,
+    #[serde(flatten)]
+    times: Times,
+    pace: Option<Duration>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    members: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    splits: Vec<Split>,
+    division: Option<String>,
+    division_rank: Option<NonZeroU16>,
+}
+
+impl TeamPlacement {
+    fn from_cells(cells: Cells) -> AResult<Self> {
+        let times = Times::new(
+            cells.optional("time"),
+            cells.optional("chiptime"),
+            cells.optional("guntime"),
+        )
+        .ok_or_else(|| anyhow!("no time in {cells:?}"))?;
+        // The members column's class isn't known for every event (it may
+        // be "members", "teammembers" or the like), so any with "member"
+        // in it will do.
+        let members = cells
+            .0
+            .iter()
+            .find_map(|&(column, text)| column.contains("member").then_some(text))
+            .map(|members| {
+                members
+                    .split([',', '/'])
+                    .map(str::trim)
+                    .filter(|member| !member.is_empty())
+                    .map(ToString::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        // Legs that haven't been run (or weren't timed) are blank.
+        let splits = cells
+            .0
+            .iter()
+            .filter(|(column, _)| column.starts_with("leg"))
+            .filter_map(|(column, text)| {
+                text.parse().ok().map(|time| Split {
+                    split: column.to_string(),
+                    time,
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            rank: cells.parsed("rank")?,
+            team_name: cells.text("name")?.to_string(),
+            bib: cells.text("bib")?.to_string(),
+            times,
+            pace: cells.optional("pace"),
+            members,
+            splits,
+            division: cells.get("agroup").map(ToString::to_string),
+            division_rank: cells.optional("agrank"),
+        })
+    }
+}
+
+/// The text of each of a row's tds, in column order, along with the
+/// column's "bazu-" class suffix (e.g. "rank", "time", "agroup").
+#[derive(Debug)]
+struct Cells<'a>(Vec<(&'a str, &'a str)>);
+
+impl<'a> Cells<'a> {
+    fn new(cells: Vec<(&'a str, &'a str)>) -> Self {
+        Self(cells)
+    }
+
+    fn get(&self, column: &str) -> Option<&'a str> {
+        self.0
+            .iter()
+            .find_map(|&(c, text)| (c == column).then_some(text))
+    }
+
+    fn text(&self, column: &str) -> AResult<&'a str> {
+        self.get(column)
+            .ok_or_else(|| anyhow!("no {column} column in {self:?}"))
+    }
+
+    fn parsed<T: FromStr>(&self, column: &str) -> AResult<T>
+    where
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        Ok(self.text(column)?.parse()?)
+    }
+
+    fn optional<T: FromStr>(&self, column: &str) -> Option<T> {
+        self.get(column)?.parse().ok()
     }
 }
 
 // Placement parsers
 
 fn placements(input: &str) -> IResult<&str, Vec<Placement>> {
-    preceded(
+    preceded(tbody, many1(map_res(cells, Placement::from_cells))).parse(input)
+}
+
+fn team_placements(input: &str) -> IResult<&str, Vec<TeamPlacement>> {
+    preceded(tbody, many1(map_res(cells, TeamPlacement::from_cells))).parse(input)
+}
+
+fn tbody(input: &str) -> IResult<&str, ()> {
+    value(
+        (),
         (
             take_until("<tbody class=\"ui-widget-content\" role=\"alert\""),
             take_until_and_consume(">"),
         ),
-        many1(placement),
     )
     .parse(input)
 }
 
-fn placement(input: &str) -> IResult<&str, Placement> {
-    map(
-        (
-            preceded(tr, map_res(td("rank"), |rank| rank.parse())),
-            td("name"),
-            td("bib"),
-            parsed_td("time"),
-            parsed_td("pace"),
-            td("hometown"),
-            opt(parsed_td("age")),
-            opt(td("sex")),
-            td("agroup"),
-            terminated(parsed_td("agrank"), close_tr),
-        ),
-        Placement::new,
-    )
-    .parse(input)
+fn cells(input: &str) -> IResult<&str, Cells<'_>> {
+    map(delimited(tr, many1(td), close_tr), Cells::new).parse(input)
 }
 
 fn tr(input: &str) -> IResult<&str, ()> {
     value((), (multispace0, tag("<tr "), take_until_and_consume(">"))).parse(input)
 }
 
-#[allow(clippy::needless_lifetimes)]
-fn parsed_td<'a, O: FromStr>(
-    to_match: &'a str,
-) -> impl Parser<&'a str, Error = Error<&'a str>, Output = O> {
-    map_res(td(to_match), |string| string.parse())
-}
-
-#[allow(clippy::needless_lifetimes)]
-fn td<'a>(to_match: &'a str) -> impl Parser<&'a str, Error = Error<&'a str>, Output = &'a str> {
-    preceded(
-        (
-            multispace0,
-            tag("<td class=\"ui-widget-content bazu-"),
-            tag(to_match),
-            tag("\">"),
-            take_until_and_consume(">"),
+// Returns the column name (the part of the class after "bazu-") and the
+// text of the td.
+fn td(input: &str) -> IResult<&str, (&str, &str)> {
+    (
+        preceded(
+            (multispace0, tag("<td class=\"ui-widget-content bazu-")),
+            map(take_until_and_consume("\">"), |class: &str| {
+                class.split(' ').next().unwrap_or(class)
+            }),
         ),
-        terminated(
-            take_until_and_consume("<"),
-            (take_until("</td>"), tag("</td>")),
+        preceded(
+            take_until_and_consume(">"),
+            terminated(
+                take_until_and_consume("<"),
+                (take_until("</td>"), tag("</td>")),
+            ),
         ),
     )
+        .parse(input)
 }
 
 fn close_tr(input: &str) -> IResult<&str, ()> {
     value((), (multispace0, tag("</tr>"))).parse(input)
 }
 
-type ValueMap<'a> = HashMap<&'a str, (&'a str, bool)>;
-
-fn value_map_from_options(input: &str) -> AResult<ValueMap<'_>> {
-    Ok(map(all_consuming(many1(option)), |v| {
-        let mut vm = ValueMap::new();
-
-        for (value, selected, menu_item) in v {
-            vm.insert(menu_item, (value, selected));
-        }
-        vm
-    })
-    .parse(input)
-    .map_err(|_| anyhow!("Could not parse {}", input))?
-    .1)
+// Each option's value, whether it's selected and its menu item, in menu
+// order.
+fn options(input: &str) -> AResult<Vec<(&str, bool, &str)>> {
+    Ok(all_consuming(many1(option))
+        .parse(input)
+        .map_err(|_| anyhow!("Could not parse {}", input))?
+        .1)
 }
 
 fn option(input: &str) -> IResult<&str, (&str, bool, &str)> {
@@ -224,37 +287,38 @@ fn option(input: &str) -> IResult<&str, (&str, bool, &str)> {
         .parse(input)
 }
 
-fn menu_item_for(race: &Race) -> &'static str {
-    use Race::*;
-
-    match race {
-        Full => "Shiprock Marathon",
-        Half => "Shiprock Half Marathon",
-        Relay => "Shiprock Marathon Relay",
-        TenK => "Shiprock 10k",
-        FiveK => "Shiprock 5k",
-        Handcycle => "Shiprock Marathon Handcycle",
-        TenKRuck => panic!("No 10k ruck at Shiprock"),
-        SoloMaleCivilianHeavy
-        | SoloMaleMilitaryHeavy
-        | SoloMaleROTCHeavy
-        | SoloFemaleMilitaryHeavy => panic!("BMDM temporary hack"),
-    }
-}
-
 pub struct Params {
-    year: Year,
-    menu_item: &'static str,
+    event_id: String,
+    race: Race,
+    paginator: Paginator,
+    retry: Retry,
 }
 
 impl Params {
+    // Any other Chronotrack event can be given by its id, which is per
+    // year, so the year is only used for Shiprock.
     pub(crate) fn new(opt: Opt) -> AResult<Self> {
-        let year = opt.year;
-        if let Err(e) = url_for_year(&year) {
-            bail!("Year {year} is not supported: {e}");
-        }
-        let menu_item = menu_item_for(&opt.race);
-        Ok(Self { year, menu_item })
+        use Event::*;
+
+        let event_id = match opt.event {
+            Shiprock => {
+                let year = opt.year();
+                match url_for_year(&year) {
+                    Err(e) => bail!("Year {year} is not supported: {e}"),
+                    Ok(event_id) => event_id.to_string(),
+                }
+            }
+            Chronotrack => opt.id()?,
+            _ => bail!("{:?} is not chronotrack", opt.event),
+        };
+        Ok(Self {
+            event_id,
+            // The grid is redrawn in place, replacing its rows.
+            paginator: Paginator::new(&opt, NEXT_PAGE, "#bazu-full-results-grid tbody tr")
+                .with_total("#bazu-full-results-grid_info"),
+            retry: Retry::new(&opt),
+            race: opt.race.unwrap_or(Race::Full),
+        })
     }
 }
 
@@ -274,16 +338,25 @@ impl Scraper for Params {
     fn url(&self) -> String {
         format!(
             "https://results.chronotrack.com/event/results/event/event-{}",
-            url_for_year(&self.year).unwrap()
+            self.event_id
         )
     }
 
     async fn doit(&self, client: &Client) -> AResult<()> {
-        let menu_item = self.menu_item;
-
-        click_the_results_tab(client).await?;
-        choose_the_race(client, menu_item).await?;
-        choose_100_per_page(client).await?;
-        Ok(extract_placements(client).await?)
+        let retry = self.retry;
+        retry.run(client, || click_the_results_tab(client)).await?;
+        retry
+            .run(client, || choose_the_race(client, &self.race))
+            .await?;
+        retry.run(client, || choose_100_per_page(client)).await?;
+        if self.race.is_relay() {
+            self.paginator
+                .paginate(client, || page(client, team_placements))
+                .await
+        } else {
+            self.paginator
+                .paginate(client, || page(client, placements))
+                .await
+        }
     }
 }
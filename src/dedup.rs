@@ -0,0 +1,67 @@
+// Finishers that are read more than once in a scrape, because a page was
+// read twice or because rows shifted from one page to the next while the
+// results were being updated.
+
+use {serde::Serialize, std::collections::HashSet, tracing::info};
+
+/// Remembers the finishers read so far by bib, name and rank, so that each
+/// is printed once, unless --keep-duplicates.
+pub(crate) struct Dedup {
+    keep: bool,
+    seen: HashSet<[String; 3]>,
+    removed: usize,
+}
+
+impl Dedup {
+    pub(crate) fn new(keep: bool) -> Self {
+        Self {
+            keep,
+            seen: HashSet::new(),
+            removed: 0,
+        }
+    }
+
+    /// The rows that haven't been read before.  Rows without a bib, name
+    /// or rank can't be told apart, so they're all kept.
+    pub(crate) fn retain<T: Serialize>(&mut self, rows: Vec<T>) -> Vec<T> {
+        if self.keep {
+            return rows;
+        }
+        let before = rows.len();
+        let rows = rows
+            .into_iter()
+            .filter(|row| key(row).is_none_or(|key| self.seen.insert(key)))
+            .collect::<Vec<_>>();
+        self.removed += before - rows.len();
+        rows
+    }
+
+    pub(crate) fn report(&self) {
+        if self.removed > 0 {
+            info!(
+                removed = self.removed,
+                "removed duplicate rows (--keep-duplicates keeps them)"
+            );
+        }
+    }
+}
+
+// Teams have a team_name rather than a name.
+fn key<T: Serialize>(row: &T) -> Option<[String; 3]> {
+    let value = serde_json::to_value(row).ok()?;
+    let field = |names: &[&str]| {
+        names
+            .iter()
+            .filter_map(|name| value.get(name))
+            .find(|v| !v.is_null())
+            .map(ToString::to_string)
+    };
+    let key = [
+        field(&["bib"]),
+        field(&["name", "team_name"]),
+        field(&["rank"]),
+    ];
+    key.iter()
+        .any(Option::is_some)
+        .then(|| key.map(Option::unwrap_or_default))
+}
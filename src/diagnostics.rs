@@ -0,0 +1,92 @@
+// When a scrape fails, what the browser was looking at (a changed layout,
+// a cookie banner, a page that never loaded) is usually the quickest way
+// to see why, so it's saved for after the browser has gone.
+
+use {
+    crate::Opt,
+    anyhow::{Error, Result as AResult},
+    clap::ValueEnum,
+    fantoccini::Client,
+    std::{
+        fs,
+        io::ErrorKind,
+        path::{Path, PathBuf},
+    },
+    tracing::error,
+};
+
+const DIAGNOSTICS_DIR: &str = "diagnostics";
+
+/// The directory failed scrapes' diagnostics go in, named after what was
+/// being scraped, e.g. "diagnostics/athlinks-half-2019".  Each failure gets
+/// a numbered directory of its own within it.
+pub(crate) fn dir(opt: &Opt) -> PathBuf {
+    let mut parts = vec![if opt.runner.is_some() {
+        "history".to_string()
+    } else if opt.table.is_some() {
+        "table".to_string()
+    } else {
+        opt.event
+            .to_possible_value()
+            .map_or_else(|| format!("{:?}", opt.event), |v| v.get_name().to_string())
+    }];
+    if let Some(race) = &opt.race {
+        parts.push(race.to_string());
+    }
+    parts.push(opt.year().to_string());
+    // Race labels can have spaces, slashes and the like in them.
+    let name = parts
+        .join("-")
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+    Path::new(DIAGNOSTICS_DIR).join(name)
+}
+
+/// Saves a screenshot, the source and the URL of the browser's page in the
+/// next numbered directory in `dir`, so that an earlier run's aren't
+/// overwritten, and returns `err` saying where they are.  Failing to save
+/// them is reported, but `err` is still the error.
+pub(crate) async fn save(c: &Client, dir: &Path, err: Error) -> Error {
+    let written = async {
+        let run = run_dir(dir)?;
+        write(c, &run).await?;
+        AResult::Ok(run)
+    };
+    match written.await {
+        Ok(dir) => err.context(format!(
+            "Scraping failed; the page it failed on is saved in {}",
+            dir.display()
+        )),
+        Err(e) => {
+            error!(dir = %dir.display(), error = ?e, "couldn't save the page");
+            err
+        }
+    }
+}
+
+// Creating the directory is what claims its number, so two runs that fail
+// at once don't share one.
+fn run_dir(dir: &Path) -> AResult<PathBuf> {
+    fs::create_dir_all(dir)?;
+    for run in 1.. {
+        let run = dir.join(run.to_string());
+        match fs::create_dir(&run) {
+            Ok(()) => return Ok(run),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    unreachable!()
+}
+
+// The URL first, since it's the least likely to fail.
+async fn write(c: &Client, dir: &Path) -> AResult<()> {
+    fs::write(dir.join("url.txt"), c.current_url().await?.as_str())?;
+    fs::write(dir.join("page.html"), c.source().await?)?;
+    fs::write(dir.join("screenshot.png"), c.screenshot().await?)?;
+    Ok(())
+}
@@ -0,0 +1,228 @@
+// Fuzzy matching of what the user asked for against the labels a results
+// site offers (race names, contests, result lists).  Labels are compared after
+// normalizing case and punctuation, and a label for a different distance
+// (e.g. "Half Marathon" when "Marathon" was asked for) never matches, no
+// matter how similar the text is.
+
+use anyhow::{bail, Result as AResult};
+
+/// Scores below this aren't considered matches at all.
+const MIN_SCORE: f64 = 0.5;
+
+/// If the runner-up scores within this of the best, we can't tell which
+/// label was meant.
+const AMBIGUITY_MARGIN: f64 = 0.05;
+
+/// Words that make a race a different race from one without them (a
+/// "Marathon Relay" is not the "Marathon").
+const MODIFIERS: [&str; 6] = ["relay", "ruck", "rucksack", "handcycle", "kids", "virtual"];
+
+/// Returns the index of the label that best matches `query`, or an error
+/// listing the candidates if none matches or if more than one matches
+/// about equally well.
+pub(crate) fn choose<S: AsRef<str>>(query: &str, labels: &[S]) -> AResult<usize> {
+    let wanted = Words::new(query);
+    let mut scored = labels
+        .iter()
+        .enumerate()
+        .map(|(i, label)| {
+            let label: &str = label.as_ref();
+            (i, label, wanted.score(&Words::new(label)))
+        })
+        .filter(|&(_, _, score)| score >= MIN_SCORE)
+        .collect::<Vec<_>>();
+    scored.sort_by(|(_, _, a), (_, _, b)| b.total_cmp(a));
+
+    match scored[..] {
+        [] => bail!(
+            "Nothing resembles {query:?} among {:?}",
+            labels.iter().map(AsRef::as_ref).collect::<Vec<&str>>()
+        ),
+        [(_, _, best), (_, _, next), ..] if best - next < AMBIGUITY_MARGIN => bail!(
+            "{query:?} is ambiguous; candidates are {:?}",
+            scored
+                .iter()
+                .map(|(_, label, score)| format!("{label} ({score:.2})"))
+                .collect::<Vec<_>>()
+        ),
+        [(i, _, _), ..] => Ok(i),
+    }
+}
+
+/// A label broken into normalized words, with a number and its unit joined
+/// back together ("10 K" and "10k" are both "10k", "100 Miler" is "100mi").
+struct Words {
+    words: Vec<String>,
+    distance: Option<String>,
+}
+
+impl Words {
+    fn new(label: &str) -> Self {
+        let lowered = label.to_lowercase();
+        let mut words = Vec::<String>::new();
+        for word in lowered
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+        {
+            let unit = match word {
+                "k" | "km" => Some("k"),
+                "mi" | "mile" | "miles" | "miler" => Some("mi"),
+                _ => None,
+            };
+            let joined = match (unit, words.last_mut()) {
+                (Some(unit), Some(last)) if last.chars().all(|c| c.is_ascii_digit()) => {
+                    last.push_str(unit);
+                    true
+                }
+                _ => false,
+            };
+            if !joined {
+                words.push(normalize_unit(word));
+            }
+        }
+        let distance = distance(&words);
+        Self { words, distance }
+    }
+
+    fn joined(&self) -> String {
+        self.words.join(" ")
+    }
+
+    fn has(&self, word: &str) -> bool {
+        self.words.iter().any(|w| w == word)
+    }
+
+    // Mostly how many of the wanted words the label has, with how similar
+    // the two are as a whole to break ties (so "Marathon" is a better
+    // match for "marathon" than "Marathon Relay" is).
+    fn score(&self, label: &Words) -> f64 {
+        if let (Some(wanted), Some(offered)) = (&self.distance, &label.distance) {
+            if wanted != offered {
+                return 0.0;
+            }
+        }
+        if self.words.is_empty() {
+            return 0.0;
+        }
+        let found = self.words.iter().filter(|w| label.has(w)).count();
+        let containment = found as f64 / self.words.len() as f64;
+        let score = 0.7 * containment + 0.3 * similarity(&self.joined(), &label.joined());
+        let unwanted = MODIFIERS
+            .iter()
+            .any(|modifier| label.has(modifier) && !self.has(modifier));
+        if unwanted {
+            score / 2.0
+        } else {
+            score
+        }
+    }
+}
+
+// "5km" => "5k", "50mile" => "50mi", etc., for units not separated from
+// their number.
+fn normalize_unit(word: &str) -> String {
+    let digits = word.chars().take_while(char::is_ascii_digit).count();
+    if digits == 0 {
+        return word.to_string();
+    }
+    match &word[digits..] {
+        "k" | "km" => format!("{}k", &word[..digits]),
+        "mi" | "mile" | "miles" | "miler" => format!("{}mi", &word[..digits]),
+        _ => word.to_string(),
+    }
+}
+
+fn distance(words: &[String]) -> Option<String> {
+    let is_distance = |w: &&String| {
+        let digits = w.chars().take_while(char::is_ascii_digit).count();
+        digits > 0 && matches!(&w[digits..], "k" | "mi")
+    };
+    if words.iter().any(|w| w == "half") {
+        Some("half".to_string())
+    } else if let Some(w) = words.iter().find(is_distance) {
+        Some(w.clone())
+    } else if words.iter().any(|w| w == "marathon") {
+        Some("marathon".to_string())
+    } else {
+        None
+    }
+}
+
+// 1.0 for identical strings down to 0.0 for ones with nothing in common.
+fn similarity(a: &str, b: &str) -> f64 {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(&a, &b) as f64 / longest as f64
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chooses_the_exact_race_over_its_relay_and_other_distances() {
+        let labels = ["Half Marathon", "Marathon", "Marathon Relay"];
+        assert_eq!(choose("marathon", &labels).unwrap(), 1);
+    }
+
+    #[test]
+    fn joins_numbers_and_units() {
+        assert_eq!(choose("10k", &["5K Run", "10 K Run"]).unwrap(), 1);
+    }
+
+    #[test]
+    fn nothing_resembling_the_query_is_an_error() {
+        let err = choose("handcycle", &["Marathon", "5K"]).unwrap_err();
+        assert!(err.to_string().starts_with("Nothing resembles"));
+    }
+
+    #[test]
+    fn near_ties_are_ambiguous() {
+        let labels = ["Marathon Relay", "Half Marathon Relay"];
+        let err = choose("relay", &labels).unwrap_err();
+        assert!(err.to_string().contains("is ambiguous"));
+    }
+
+    #[test]
+    fn unrelated_labels_score_below_the_minimum() {
+        let score = Words::new("handcycle").score(&Words::new("marathon"));
+        assert!(score < MIN_SCORE);
+        let score = Words::new("marathon").score(&Words::new("Marathon"));
+        assert!(score >= MIN_SCORE);
+    }
+
+    #[test]
+    fn distances() {
+        let distance = |label| Words::new(label).distance;
+        assert_eq!(distance("Half Marathon").as_deref(), Some("half"));
+        assert_eq!(distance("100 Miler").as_deref(), Some("100mi"));
+        assert_eq!(distance("5km Run").as_deref(), Some("5k"));
+        assert_eq!(distance("Marathon Relay").as_deref(), Some("marathon"));
+        assert_eq!(distance("Fun Run"), None);
+    }
+
+    #[test]
+    fn levenshtein_distances() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(levenshtein(&chars("kitten"), &chars("sitting")), 3);
+        assert_eq!(levenshtein(&chars(""), &chars("abc")), 3);
+        assert_eq!(levenshtein(&chars("relay"), &chars("relay")), 0);
+    }
+}
@@ -1,6 +1,12 @@
 use {
-    crate::{Event, Opt, Race, Scraper, Year},
-    anyhow::{anyhow, bail, Result as AResult},
+    crate::{
+        paginator::{NextPage, Paginator},
+        progress,
+        retry::Retry,
+        times::Times,
+        ClientExt, Event, Opt, Race, Scraper, Year,
+    },
+    anyhow::{anyhow, bail, Context, Result as AResult},
     async_trait::async_trait,
     digital_duration_nom::duration::Duration,
     fantoccini::{
@@ -9,134 +15,80 @@ use {
         Client,
         Locator::Css,
     },
-    futures::stream::{self, StreamExt},
+    futures::{
+        future,
+        stream::{self, StreamExt, TryStreamExt},
+    },
     serde::Serialize,
-    std::num::NonZeroU16,
+    std::{num::NonZeroU16, str::FromStr},
+    tracing::{debug, instrument, warn},
 };
 
 pub struct Params {
     event_id: u32,
-    race_menus: &'static [&'static str],
+    race: Option<Race>,
     year: Year,
     participant: bool,
+    paginator: Paginator,
+    retry: Retry,
 }
 
-static SOLO_MALE_CIVILIAN_HEAVIES: [&str; 2] = [
-    "CIVILIAN Male Heavy",            // 2016
-    "Individual CIVILIAN Male Heavy", // 2017 - 2019
-];
-
-static SOLO_MALE_MILITARY_HEAVIES: [&str; 2] = [
-    "MILITARY Male Heavy",            // 2016
-    "Individual MILITARY Male Heavy", // 2017 - 2019
-];
-
-static SOLO_MALE_ROTC_HEAVIES: [&str; 2] = [
-    "ROTC Male Heavy",            // 2016
-    "Individual ROTC Male Heavy", // 2017 - 2019
-];
-
-static SOLO_FEMALE_MILITARY_HEAVIES: [&str; 2] = [
-    "MILITARY Female Heavy",            // 2016
-    "Individual MILITARY Female Heavy", // 2017 - 2019
-];
-
 impl Params {
     pub(crate) fn new(opt: Opt) -> AResult<Self> {
         use Event::*;
 
         match opt.event {
             BMDM => Self::new_bmdm(opt),
+            ItsYourRace => Ok(Self {
+                event_id: opt.id()?,
+                year: opt.year(),
+                paginator: paginator(&opt),
+                retry: Retry::new(&opt),
+                race: opt.race,
+                participant: opt.participant,
+            }),
             _ => bail!("{:?} is not It's Your Race", opt.event),
         }
     }
 
+    // The BMDM race labels changed after 2016 (e.g. "CIVILIAN Male Heavy"
+    // became "Individual CIVILIAN Male Heavy"), so a pattern like
+    // "*CIVILIAN Male Heavy" is the way to select the same race every year.
     fn new_bmdm(opt: Opt) -> AResult<Self> {
-        use Race::*;
-
-        let race_menus = match opt.race {
-            SoloMaleCivilianHeavy => &SOLO_MALE_CIVILIAN_HEAVIES[..],
-            SoloMaleMilitaryHeavy => &SOLO_MALE_MILITARY_HEAVIES[..],
-            SoloFemaleMilitaryHeavy => &SOLO_FEMALE_MILITARY_HEAVIES[..],
-            SoloMaleROTCHeavy => &SOLO_MALE_ROTC_HEAVIES[..],
-            _ => bail!("Only some are available (see the source code)"),
-        };
-
         Ok(Self {
             event_id: 6574,
-            race_menus,
-            year: opt.year,
+            year: opt.year(),
+            paginator: paginator(&opt),
+            retry: Retry::new(&opt),
+            race: opt.race,
             participant: opt.participant,
         })
     }
 }
 
-const NEXT_LINK_CSS: &str = "#btnNext";
-
-async fn print_placements(c: &Client) -> AResult<()> {
-    c.wait().for_element(Css("#ddlPage")).await?;
-    // Yes, they really reuse Tr1 in all their trs.
-    let placements = stream::iter(c.find_all(Css("tr#Tr1")).await?)
-        .filter_map(Placement::from_element)
-        .collect::<Vec<_>>()
-        .await;
-    println!("{}", serde_json::to_string(&placements).unwrap());
-    Ok(())
-}
-
-async fn print_participants(c: &Client) -> AResult<()> {
-    c.wait().for_element(Css("#ddlPage")).await?;
-    // Yes, they really reuse Tr1 in all their trs.
-    let participants = stream::iter(c.find_all(Css("tr#Tr1")).await?)
-        .filter_map(Participant::from_element)
-        .collect::<Vec<_>>()
-        .await;
-    println!("{}", serde_json::to_string(&participants).unwrap());
-    Ok(())
-}
-
-async fn next_button(c: &Client) -> AResult<Option<Element>> {
-    match c.find(Css(NEXT_LINK_CSS)).await {
-        Ok(element) => Ok(Some(element)),
-        Err(CmdError::Standard(WebDriver {
-            error: NoSuchElement,
-            ..
-        })) => Ok(None),
-        Err(err) => bail!(err),
-    }
+// Clicking next posts the page back, which replaces every row.
+fn paginator(opt: &Opt) -> Paginator {
+    Paginator::new(opt, NextPage::UnlessMissing("#btnNext"), ROWS_CSS)
 }
 
-async fn extract_placements(c: &Client) -> AResult<()> {
-    let mut button;
+// Yes, they really reuse Tr1 in all their trs.
+const ROWS_CSS: &str = "tr#Tr1";
 
-    while {
-        print_placements(c).await?;
-        button = next_button(c).await?;
-        button.is_some()
-    } {
-        button.unwrap().click().await?;
-    }
-    Ok(())
+async fn placements(c: &Client) -> AResult<Vec<Placement>> {
+    c.wait_for(Css("#ddlPage")).await?;
+    Ok(stream::iter(c.find_all(Css(ROWS_CSS)).await?)
+        .filter_map(Placement::from_element)
+        .collect()
+        .await)
 }
 
-// TODO: DRY this with extract_placements.  It's trivial to do with macros,
-//       but without being able to have an async fn passed in as a
-//       parameter, it's a bit hard without macros (although that may
-//       be due to my lack of Rust knowledge).  There's a discussion
-//       about emulating async fn pointers on Stack Overflow:
-//       https://stackoverflow.com/questions/66769143/rust-passing-async-function-pointers
-
-async fn extract_participants(c: &Client) -> AResult<()> {
-    let mut button;
-
-    while {
-        print_participants(c).await?;
-        button = next_button(c).await?;
-        button.is_some()
-    } {
-        button.unwrap().click().await?;
-    }
-    Ok(())
+async fn participants(c: &Client, race: Option<&str>) -> AResult<Vec<Participant>> {
+    c.wait_for(Css("#ddlPage")).await?;
+    Ok(stream::iter(c.find_all(Css(ROWS_CSS)).await?)
+        .filter_map(Participant::from_element)
+        .filter(|p| future::ready(race.is_none_or(|race| p.race == race)))
+        .collect()
+        .await)
 }
 
 #[derive(Serialize)]
@@ -144,7 +96,8 @@ struct Placement {
     rank: NonZeroU16,
     name: String,
     bib: String,
-    time: Duration,
+    #[serde(flatten)]
+    times: Times,
     pace: Duration,
 }
 
@@ -160,6 +113,12 @@ macro_rules! parsed_element_text {
     };
 }
 
+// Unlike parsed_element_text!, a missing or unparsable cell doesn't cause
+// the enclosing function to return.
+async fn optional_parsed_text<T: FromStr>(e: &Element, css: &str) -> Option<T> {
+    e.find(Css(css)).await.ok()?.text().await.ok()?.parse().ok()
+}
+
 impl Placement {
     async fn from_element(e: Element) -> Option<Self> {
         async fn from_element(e: &Element) -> Option<Placement> {
@@ -169,23 +128,27 @@ impl Placement {
                 let name_and_bib = element_text!(e, ".name")?;
                 let pieces = name_and_bib.split(" (# ").collect::<Vec<_>>();
                 if pieces.len() != 2 {
-                    eprintln!("expected two pieces in {name_and_bib}");
+                    debug!(%name_and_bib, "expected two pieces");
                     return None;
                 }
                 match pieces[1].find(')') {
                     None => {
-                        eprintln!("couldn't find closing paren in {}", pieces[1]);
+                        debug!(%name_and_bib, "couldn't find closing paren");
                         return None;
                     }
                     Some(n) => (pieces[0].to_string(), pieces[1][..n].to_string()),
                 }
             };
-            let time = parsed_element_text!(e, ".chiptime")?;
+            let times = {
+                let chip_time = optional_parsed_text(e, ".chiptime").await;
+                let gun_time = optional_parsed_text(e, ".guntime").await;
+                Times::new(None, chip_time, gun_time)?
+            };
             let pace = {
                 let pace = element_text!(e, ".pace")?;
                 match pace.strip_suffix("/mile") {
                     None => {
-                        eprintln!("Couldn't find /mile in {pace}");
+                        debug!(%pace, "couldn't find /mile");
                         return None;
                     }
                     Some(pace) => pace.parse().ok()?,
@@ -195,7 +158,7 @@ impl Placement {
                 rank,
                 name,
                 bib,
-                time,
+                times,
                 pace,
             })
         }
@@ -212,18 +175,18 @@ impl Placement {
                     }
                 }
             }
-            eprintln!("discarding {text:?}");
+            progress::discarded();
+            warn!(?text, "discarding row");
         }
         result
     }
 }
 
 async fn pop_up_select(c: &Client, selector: &str, matches: &[&str]) -> AResult<()> {
-    let e = c.wait().for_element(Css(selector)).await.map_err(|e| {
-        let message = format!("Couldn't find {selector}: {e:?}");
-        eprintln!("{}", message);
-        anyhow!(message)
-    })?;
+    let e = c
+        .wait_for(Css(selector))
+        .await
+        .with_context(|| format!("Couldn't find {selector}"))?;
 
     let mut found = false;
     for label in matches.iter() {
@@ -236,9 +199,8 @@ async fn pop_up_select(c: &Client, selector: &str, matches: &[&str]) -> AResult<
                 error: NoSuchElement,
                 ..
             })) => {} // ignore
-            Err(e) => {
-                dbg!(e);
-            } // this is a surprise
+            // this is a surprise
+            Err(e) => warn!(selector, label, error = ?e, "couldn't select"),
         }
     }
     if !found {
@@ -247,16 +209,43 @@ async fn pop_up_select(c: &Client, selector: &str, matches: &[&str]) -> AResult<
     Ok(())
 }
 
+#[instrument(skip(c))]
 async fn select_year(c: &Client, year: Year) -> AResult<()> {
     let year = year.to_string();
     let years = [year.as_ref()];
     pop_up_select(c, "#ddlYear", &years[..]).await
 }
 
-async fn select_race(c: &Client, race_menus: &[&str]) -> AResult<()> {
-    pop_up_select(c, "#ddlRace", race_menus).await
+const RACE_SELECTOR: &str = "#ddlRace";
+
+// Returns the label of the race, as it appears in the race menu.
+#[instrument(skip(c))]
+async fn race_label(c: &Client, race: Option<&Race>) -> AResult<String> {
+    let e = c.wait_for(Css(RACE_SELECTOR)).await?;
+    let mut labels = stream::iter(e.find_all(Css("option")).await?)
+        .then(|option| async move { option.text().await })
+        .try_collect::<Vec<_>>()
+        .await?;
+    let race = match race {
+        None if labels.len() == 1 => return Ok(labels.remove(0)),
+        None => bail!("Choose a race with --race; {RACE_SELECTOR} has {labels:?}"),
+        Some(race) => race,
+    };
+    // Race names vary too much from one It's Your Race event to the next
+    // to have aliases for the shorthands, so they're only matched fuzzily.
+    let index = race
+        .choose(&labels, &[])
+        .map_err(|e| anyhow!("{RACE_SELECTOR}: {e}"))?;
+    Ok(labels.swap_remove(index))
+}
+
+#[instrument(skip(c))]
+async fn select_race(c: &Client, race: Option<&Race>) -> AResult<()> {
+    let label = race_label(c, race).await?;
+    pop_up_select(c, RACE_SELECTOR, &[&label]).await
 }
 
+#[instrument(skip_all)]
 async fn select_participant(c: &Client) -> AResult<()> {
     let link = c.find(Css("#lnkParticipants")).await?;
     link.click().await?;
@@ -273,13 +262,24 @@ impl Scraper for Params {
     }
 
     async fn doit(&self, client: &Client) -> AResult<()> {
+        let retry = self.retry;
+        retry.run(client, || select_year(client, self.year)).await?;
         if self.participant {
-            select_participant(client).await?;
-            extract_participants(client).await
+            // The participant list has every race in it, so rather than
+            // requiring a race, we only filter by one if it's given.
+            let race = match &self.race {
+                None => None,
+                Some(race) => Some(retry.run(client, || race_label(client, Some(race))).await?),
+            };
+            retry.run(client, || select_participant(client)).await?;
+            self.paginator
+                .paginate(client, || participants(client, race.as_deref()))
+                .await
         } else {
-            select_year(client, self.year).await?;
-            select_race(client, self.race_menus).await?;
-            extract_placements(client).await
+            retry
+                .run(client, || select_race(client, self.race.as_ref()))
+                .await?;
+            self.paginator.paginate(client, || placements(client)).await
         }
     }
 }
@@ -288,7 +288,7 @@ impl Scraper for Params {
 struct Participant {
     name: String,
     bib: String,
-    hometown: String,
+    hometown: Option<String>,
     race: String,
     age_group: String,
 }
@@ -299,18 +299,25 @@ impl Participant {
         let _ = tds.next()?;
         let (name, bib, hometown) = {
             let name_bib_hometown = tds.next()?.text().await.ok()?;
-            let pieces = name_bib_hometown.split('\n').collect::<Vec<_>>();
-            if pieces.len() != 2 {
-                eprintln!("expected one newline in {name_bib_hometown}");
+            // The hometown is on a line of its own, when there is one.
+            let mut lines = name_bib_hometown.split('\n');
+            let name_and_bib = lines.next()?;
+            let hometown = lines
+                .next()
+                .map(str::trim)
+                .filter(|hometown| !hometown.is_empty())
+                .map(ToString::to_string);
+            if lines.next().is_some() {
+                warn!(%name_bib_hometown, "expected at most one newline");
                 return None;
             }
-            let sub_pieces = pieces[0].split(" ( Bib # ").collect::<Vec<_>>();
+            let sub_pieces = name_and_bib.split(" ( Bib # ").collect::<Vec<_>>();
             match sub_pieces.len() {
-                1 => (pieces[0].to_string(), String::new(), pieces[1].to_string()),
+                1 => (name_and_bib.to_string(), String::new(), hometown),
                 2 => {
                     let end = match sub_pieces[1].find(" )") {
                         None => {
-                            eprintln!("Couldn't find closing paren in {name_bib_hometown}");
+                            warn!(%name_bib_hometown, "couldn't find closing paren");
                             return None;
                         }
                         Some(end) => end,
@@ -318,11 +325,11 @@ impl Participant {
                     (
                         sub_pieces[0].to_string(),
                         sub_pieces[1][..end].to_string(),
-                        pieces[1].to_string(),
+                        hometown,
                     )
                 }
                 _n => {
-                    eprintln!("found multiple bibs in {name_bib_hometown}");
+                    warn!(%name_bib_hometown, "found multiple bibs");
                     return None;
                 }
             }
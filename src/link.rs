@@ -0,0 +1,110 @@
+// Choosing the event from a results link pasted from a browser, so that
+// the site's ids don't have to be picked out of it by hand.
+
+use {
+    crate::{Event, Opt, Year},
+    anyhow::{anyhow, bail, Result as AResult},
+    url::Url,
+};
+
+/// Sets the event and its --id from the link in --url.  The race is left
+/// as given.  Sites whose links are for an event of every year need the
+/// year, which is the link's when it has one and must otherwise be given
+/// with --year, since the default year is only for the events we know.  A
+/// link with a --table selector is scraped as is.
+pub(crate) fn apply(opt: &mut Opt) -> AResult<()> {
+    use Event::*;
+
+    let url = match &opt.url {
+        Some(url) if opt.table.is_none() => url,
+        _ => return Ok(()),
+    };
+    let (event, id) = event_and_id(url)?;
+    if opt.year.is_none() && matches!(event, ItsYourRace | UltraSignup) {
+        opt.year = year(url);
+    }
+    if opt.year.is_none() && matches!(event, ItsYourRace | UltraSignup | RunSignup) {
+        bail!("{url} doesn't say which year's results to get; give the year with --year");
+    }
+    opt.event = event;
+    opt.id = Some(id);
+    Ok(())
+}
+
+// e.g. https://www.itsyourrace.com/Results.aspx?id=6574&y=2019
+fn year(url: &Url) -> Option<Year> {
+    url.query_pairs()
+        .find(|(k, _)| k.eq_ignore_ascii_case("y") || k.eq_ignore_ascii_case("year"))
+        .and_then(|(_, v)| v.parse().ok())
+}
+
+fn event_and_id(url: &Url) -> AResult<(Event, String)> {
+    use Event::*;
+
+    let host = url.host_str().unwrap_or_default().to_lowercase();
+    let segments = url
+        .path_segments()
+        .map(|segments| segments.filter(|s| !s.is_empty()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let on = |site: &str| host == site || host.ends_with(&format!(".{site}"));
+    let after = |segment: &str| {
+        segments
+            .iter()
+            .position(|s| s.eq_ignore_ascii_case(segment))
+            .and_then(|i| segments.get(i + 1))
+            .map(|s| s.to_string())
+    };
+    let query = |key: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.into_owned())
+    };
+    let missing = || anyhow!("Couldn't find the event's id in {url}");
+
+    Ok(if on("athlinks.com") {
+        // e.g. https://www.athlinks.com/event/34346/results/Event/729962/Results
+        match segments[..] {
+            [_event, event_id, _results, _event_again, second_id, ..] => {
+                (Athlinks, format!("{event_id}/{second_id}"))
+            }
+            _ => return Err(missing()),
+        }
+    } else if on("results.chronotrack.com") {
+        // e.g. https://results.chronotrack.com/event/results/event/event-40479
+        let id = segments
+            .iter()
+            .copied()
+            .find_map(|s| s.strip_prefix("event-"))
+            .ok_or_else(missing)?;
+        (Chronotrack, id.to_string())
+    } else if on("itsyourrace.com") {
+        // e.g. https://www.itsyourrace.com/Results.aspx?id=6574
+        (ItsYourRace, query("id").ok_or_else(missing)?)
+    } else if on("ultrasignup.com") {
+        // e.g. https://ultrasignup.com/register.aspx?did=72701
+        (UltraSignup, query("did").ok_or_else(missing)?)
+    } else if on("runsignup.com") {
+        // e.g. https://runsignup.com/Race/Results/12345
+        (RunSignup, after("results").ok_or_else(missing)?)
+    } else if on("raceresult.com") {
+        // e.g. https://my.raceresult.com/123456/results
+        (
+            RaceResult,
+            segments.first().ok_or_else(missing)?.to_string(),
+        )
+    } else if on("webscorer.com") {
+        // e.g. https://www.webscorer.com/race?raceid=123456
+        (Webscorer, query("raceid").ok_or_else(missing)?)
+    } else if on("raceroster.com") {
+        // e.g. https://results.raceroster.com/v2/en-US/results/vxb8q7z6cfmwf2ny/results
+        (RaceRoster, after("results").ok_or_else(missing)?)
+    } else if on("opensplittime.org") {
+        // e.g. https://www.opensplittime.org/events/moab-240-2023/spread
+        (OpenSplitTime, after("events").ok_or_else(missing)?)
+    } else if on("sporthive.com") {
+        // e.g. https://results.sporthive.com/events/7051437383614316288/races/1
+        (Sporthive, after("events").ok_or_else(missing)?)
+    } else {
+        bail!("Don't know how to scrape {url}")
+    })
+}
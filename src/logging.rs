@@ -0,0 +1,27 @@
+// What the scrapers report along the way (discarded rows, retries, pages)
+// goes to stderr as tracing events, so that stdout is only results.
+
+use {crate::Opt, tracing::Level, tracing_subscriber::EnvFilter};
+
+/// Logs our own events at the level -v and -q choose (info unless told
+/// otherwise) and everybody else's warnings, unless RUST_LOG says what
+/// to log instead.
+pub(crate) fn init(opt: &Opt) {
+    let level = match i16::from(opt.verbose) - i16::from(opt.quiet) {
+        ..=-2 => Level::ERROR,
+        -1 => Level::WARN,
+        0 => Level::INFO,
+        1 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(format!("warn,{}={level}", env!("CARGO_CRATE_NAME"))));
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    if opt.log_json {
+        subscriber.json().init();
+    } else {
+        subscriber.init();
+    }
+}
@@ -1,11 +1,12 @@
 use {
-    anyhow::Result as AResult,
+    anyhow::{anyhow, bail, Context, Result as AResult},
     async_trait::async_trait,
-    clap::{Parser, ValueEnum},
+    clap::{ArgAction, Parser, ValueEnum},
     fantoccini::{
         actions::{InputSource, NullAction, NullActions},
         elements::Element,
-        Client, ClientBuilder,
+        error::CmdError,
+        Client, ClientBuilder, Locator,
     },
     nom::{
         bytes::complete::{take, take_until},
@@ -15,22 +16,59 @@ use {
     },
     serde_json::value,
     std::{
+        convert::Infallible,
         fmt::{self, Display, Formatter},
-        num::ParseIntError,
+        num::{NonZeroU16, NonZeroU8, ParseIntError},
         str::FromStr,
+        sync::OnceLock,
+        time::Duration,
     },
+    tracing::{info_span, Instrument},
+    url::Url,
 };
 
 mod athlinks;
 mod chronotrack;
+mod dedup;
+mod diagnostics;
+mod fuzzy;
 mod its_your_race;
+mod link;
+mod logging;
+mod opensplittime;
+mod paginator;
+mod placement;
+mod progress;
+mod race_roster;
+mod raceresult;
+mod retry;
+mod row;
+mod runsignup;
+mod sporthive;
+mod table;
+mod times;
 mod ultrasignup;
+mod webscorer;
 
 #[tokio::main]
 async fn main() -> AResult<()> {
     use Event::*;
 
-    let opt = Opt::parse();
+    let mut opt = Opt::parse();
+    logging::init(&opt);
+    link::apply(&mut opt)?;
+    // --event has a default, so clap can't tell whether it was given.
+    if opt.histories
+        && !matches!(
+            opt.event,
+            Moab240 | JJ100 | DPTR | BosqueBigfoot | UltraSignup
+        )
+    {
+        bail!(
+            "--histories is only for UltraSignup events, not {:?}",
+            opt.event
+        );
+    }
 
     let mut caps = serde_json::map::Map::new();
 
@@ -40,32 +78,86 @@ async fn main() -> AResult<()> {
         serde_json::json!({ "args": ["--headless"] })
     };
     caps.insert("moz:firefoxOptions".to_string(), firefox_opts);
+    // Only the timeouts that are given, so the driver's defaults stand for
+    // the rest.
+    let mut timeouts = serde_json::map::Map::new();
+    if let Some(ms) = opt.implicit_wait {
+        timeouts.insert("implicit".to_string(), ms.into());
+    }
+    if let Some(ms) = opt.page_load_timeout {
+        timeouts.insert("pageLoad".to_string(), ms.into());
+    }
+    if !timeouts.is_empty() {
+        caps.insert("timeouts".to_string(), timeouts.into());
+    }
+    if let Some(ms) = opt.wait_timeout {
+        WAIT_TIMEOUT.set(Duration::from_millis(ms)).unwrap();
+    }
 
     // let mut c = Client::with_capabilities("http://localhost:4444", caps).await?;
 
     let c = ClientBuilder::native()
         .capabilities(caps)
-        .connect("http://localhost:4444")
-        .await?;
+        .connect(opt.webdriver.as_str())
+        .await
+        .map_err(|e| {
+            anyhow!(
+                "Couldn't connect to the WebDriver at {}: {e}",
+                opt.webdriver
+            )
+        })?;
+    if let Some(WindowSize { width, height }) = opt.window_size {
+        c.set_window_size(width, height).await?;
+    }
+
+    let retry = retry::Retry::new(&opt);
+    let diagnostics = diagnostics::dir(&opt);
 
-    let scraper: Box<dyn Scraper + Sync> = match opt.event {
-        Shiprock => Box::new(chronotrack::Params::new(opt)?),
-        Rftz | Lt100 | DukeCityMarathon | CorralesDitchRun | KotH | RioGrande | DoggieDash => {
-            Box::new(athlinks::Params::new(opt)?)
+    let scraper: Box<dyn Scraper + Sync> = if opt.runner.is_some() {
+        Box::new(ultrasignup::History::new(opt)?)
+    } else if opt.table.is_some() {
+        Box::new(table::Params::new(opt)?)
+    } else {
+        match opt.event {
+            Shiprock | Chronotrack => Box::new(chronotrack::Params::new(opt)?),
+            Rftz | Lt100 | DukeCityMarathon | CorralesDitchRun | KotH | RioGrande | DoggieDash
+            | Athlinks => Box::new(athlinks::Params::new(opt)?),
+            Moab240 | JJ100 | DPTR | BosqueBigfoot | UltraSignup => {
+                Box::new(ultrasignup::Params::new(opt)?)
+            }
+            BMDM | ItsYourRace => Box::new(its_your_race::Params::new(opt)?),
+            RunSignup => Box::new(runsignup::Params::new(opt)?),
+            RaceResult => Box::new(raceresult::Params::new(opt)?),
+            Webscorer => Box::new(webscorer::Params::new(opt)?),
+            RaceRoster => Box::new(race_roster::Params::new(opt)?),
+            OpenSplitTime => Box::new(opensplittime::Params::new(opt)?),
+            Sporthive => Box::new(sporthive::Params::new(opt)?),
         }
-        Moab240 | JJ100 | DPTR | BosqueBigfoot => Box::new(ultrasignup::Params::new(opt)?),
-        BMDM => Box::new(its_your_race::Params::new(opt)?),
     };
 
     let url = scraper.url();
 
-    c.goto(&url).await?;
-    c.persist().await?;
-    scraper.doit(&c).await?;
+    let scraped = async {
+        retry
+            .run(&c, || c.goto(&url))
+            .instrument(info_span!("navigate"))
+            .await?;
+        c.persist().await?;
+        scraper.doit(&c).await
+    }
+    .instrument(info_span!("scrape", %url))
+    .await;
+    if let Err(err) = scraped {
+        let err = diagnostics::save(&c, &diagnostics, err).await;
+        c.close().await?;
+        return Err(err);
+    }
     c.close().await?;
     Ok(())
 }
 
+const OBSCURED_CLICK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
 #[async_trait]
 trait ElementExt {
     async fn really_click(&self, client: &Client) -> AResult<()>;
@@ -81,18 +173,19 @@ impl ElementExt for Element {
         Ok(())
     }
 
+    // Gives up once whatever is on top of the element has had
+    // OBSCURED_CLICK_TIMEOUT to go away.
     async fn obscured_click(&self, c: &Client) -> AResult<()> {
-        while self
-            .click()
-            .await
-            .inspect_err(|_e| {
-                // dbg!(e);
-            })
-            .is_err()
-        {
-            c.pause(std::time::Duration::from_millis(250)).await?;
+        let start = std::time::Instant::now();
+        loop {
+            match self.click().await {
+                Ok(()) => return Ok(()),
+                Err(e) if start.elapsed() >= OBSCURED_CLICK_TIMEOUT => {
+                    bail!("still couldn't click after {OBSCURED_CLICK_TIMEOUT:?}: {e}")
+                }
+                Err(_) => c.pause(std::time::Duration::from_millis(250)).await?,
+            }
         }
-        Ok(())
     }
 }
 
@@ -111,22 +204,132 @@ fn take_until_and_consume<'a>(
 
 #[derive(Parser, Debug)]
 pub(crate) struct Opt {
-    /// shiprock, rftz, lt100 or moab240
+    /// shiprock, rftz, lt100 or moab240, or athlinks, chronotrack,
+    /// its-your-race, ultra-signup, run-signup, race-result, webscorer,
+    /// race-roster, open-split-time or sporthive (with --id) for any event
+    /// on that site
     #[arg(short, long, default_value = "shiprock", value_enum)]
     pub event: Event,
-    /// full, half, relay, 10k, 5k or handcycle
-    #[arg(short, long, default_value = "full", value_enum)]
-    pub race: Race,
-    #[arg(short, long, default_value = "2019")]
-    pub year: Year,
+    /// The site's id for the event, for the events that are a whole site,
+    /// e.g. the id in an It's Your Race results URL, an UltraSignup did,
+    /// the event name in an OpenSplitTime URL (moab-240-2023) or both of
+    /// the ids in an Athlinks results URL (34346/729962)
+    #[arg(long)]
+    pub id: Option<String>,
+    /// A results page's link, instead of --event and --id
+    #[arg(long, conflicts_with_all = ["event", "id"])]
+    pub url: Option<Url>,
+    /// For sites without a scraper of their own, the selector of the
+    /// --url page's results table (e.g. "table#results")
+    #[arg(long, requires = "url")]
+    pub table: Option<String>,
+    /// Which columns of the --table hold which fields, numbering columns
+    /// from 1 (e.g. "1=rank,2=name,5=time"); the fields are rank, name,
+    /// bib, gender, age, division, division_rank, hometown, time,
+    /// chip_time, gun_time and pace [default: the table's headings]
+    #[arg(long, requires = "table")]
+    pub columns: Option<table::Columns>,
+    /// The selector of the --table page's link to its next page, if the
+    /// results are on more than one page
+    #[arg(long, requires = "table")]
+    pub next: Option<String>,
+    /// full, half, relay, 10k, 5k, handcycle or 10kruck, or the race's
+    /// label exactly as the results site shows it (e.g. "50 Miler"), or a
+    /// pattern for the label using * and ? as wildcards (e.g. "*Male Heavy")
+    /// [default: full, except for participant lists, which default to
+    /// every race]
+    #[arg(short, long)]
+    pub race: Option<Race>,
+    /// [default: 2019, except with --url, where it's the link's year; an
+    /// It's Your Race, UltraSignup or RunSignup link without one needs it]
+    #[arg(short, long)]
+    pub year: Option<Year>,
+    /// Stop after this many pages of results
+    #[arg(long)]
+    pub max_pages: Option<NonZeroU16>,
+    /// How many milliseconds to wait between pages of results, on top of
+    /// waiting for the next page to replace the old one [default: 0]
+    #[arg(long)]
+    pub page_delay: Option<u64>,
+    /// Print finishers who are read more than once (e.g. from a page read
+    /// twice) each time they're read, rather than once
+    #[arg(long)]
+    pub keep_duplicates: bool,
+    /// How many times to try a step that fails because of WebDriver
+    /// (e.g. an element redrawn as it's read) before giving up
+    /// [default: 3]
+    #[arg(long)]
+    pub attempts: Option<NonZeroU8>,
+    /// How many milliseconds to wait before trying a step again, doubling
+    /// with each try [default: 500]
+    #[arg(long)]
+    pub backoff: Option<u64>,
+    /// The WebDriver server (e.g. geckodriver) that drives the browser
+    #[arg(long, default_value = "http://localhost:4444")]
+    pub webdriver: Url,
+    /// How many milliseconds the driver keeps looking for an element
+    /// before deciding it isn't there [default: the driver's, usually 0]
+    #[arg(long)]
+    pub implicit_wait: Option<u64>,
+    /// How many milliseconds to wait for an element that the page is
+    /// expected to show [default: 30000]
+    #[arg(long)]
+    pub wait_timeout: Option<u64>,
+    /// How many milliseconds a page has to load [default: the driver's,
+    /// usually 300000]
+    #[arg(long)]
+    pub page_load_timeout: Option<u64>,
+    /// The browser window's size, e.g. 1920x1080, since some sites lay
+    /// out their results differently in narrower windows [default: the
+    /// browser's]
+    #[arg(long)]
+    pub window_size: Option<WindowSize>,
     /// See the webpage as results are gathered
     #[arg(short, long)]
     pub display: bool,
     #[arg(short, long)]
     pub participant: bool,
+    /// Instead of an event's results, get this runner's UltraSignup
+    /// results history ("First Last" or "Last, First")
+    #[arg(long)]
+    pub runner: Option<String>,
+    /// After scraping an UltraSignup event's results, also get every
+    /// finisher's UltraSignup results history
+    #[arg(long, conflicts_with_all = ["runner", "table", "participant"])]
+    pub histories: bool,
+    /// Log more of what's going on (-vv for even more)
+    #[arg(short, long, action = ArgAction::Count)]
+    pub verbose: u8,
+    /// Log less, only warnings (-qq for only errors)
+    #[arg(short, long, action = ArgAction::Count, conflicts_with = "verbose")]
+    pub quiet: u8,
+    /// Log as JSON, one object per line
+    #[arg(long)]
+    pub log_json: bool,
 }
 
-#[derive(Clone, Copy, Debug, ValueEnum)]
+impl Opt {
+    pub(crate) fn year(&self) -> Year {
+        self.year.unwrap_or(Year(2019))
+    }
+
+    /// The --id, which the site-wide events require.
+    pub(crate) fn id<T: FromStr>(&self) -> AResult<T>
+    where
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        match &self.id {
+            None => bail!("{:?} needs the event's --id", self.event),
+            Some(id) => Ok(id.parse()?),
+        }
+    }
+}
+
+/// The race within an event.  The shorthands are mapped to each site's
+/// labels by that site's module; Label and Pattern are matched against the
+/// labels the site offers, so any race can be chosen without adding a
+/// variant here.
+#[derive(Clone, Debug)]
 pub enum Race {
     Full,
     Half,
@@ -135,47 +338,143 @@ pub enum Race {
     FiveK,
     Handcycle,
     TenKRuck,
-    SoloMaleCivilianHeavy,   // TEMPORARY HACK
-    SoloMaleMilitaryHeavy,   // TEMPORARY HACK
-    SoloMaleROTCHeavy,       // TEMPORARY HACK
-    SoloFemaleMilitaryHeavy, // TEMPORARY HACK
+    Label(String),
+    Pattern(String),
 }
 
-#[derive(Debug)]
-pub struct ParseRaceError;
+impl FromStr for Race {
+    type Err = Infallible;
 
-impl Display for ParseRaceError {
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Race::*;
+
+        Ok(match s {
+            "full" => Full,
+            "half" => Half,
+            "relay" => Relay,
+            "10k" => TenK,
+            "5k" => FiveK,
+            "handcycle" => Handcycle,
+            "10kruck" => TenKRuck,
+            _ if s.contains(['*', '?']) => Pattern(s.to_string()),
+            _ => Label(s.to_string()),
+        })
+    }
+}
+
+impl Display for Race {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(
-            f,
-            "choose \"full\", \"half\", \"relay\", \"10k\", \"5k\" or \"handcycle\" (or \"solo-male-heavy\")"
-        )
+        use Race::*;
+
+        match self {
+            Full => "full",
+            Half => "half",
+            Relay => "relay",
+            TenK => "10k",
+            FiveK => "5k",
+            Handcycle => "handcycle",
+            TenKRuck => "10kruck",
+            Label(s) | Pattern(s) => s,
+        }
+        .fmt(f)
     }
 }
 
-impl FromStr for Race {
-    type Err = ParseRaceError;
+impl Race {
+    /// Returns the index of the label, of those a site offers, that this
+    /// race refers to.  `aliases` are the site's labels for the shorthand
+    /// variants that fuzzy matching wouldn't find; the label that is one
+    /// of them (or that is exactly the given label) is chosen.  Failing
+    /// that, the label is chosen fuzzily.  Either way, more than one match
+    /// is an error, as is a pattern that doesn't match any label.
+    pub(crate) fn choose<S: AsRef<str>>(&self, labels: &[S], aliases: &[&str]) -> AResult<usize> {
+        use Race::*;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let labels = labels.iter().map(AsRef::as_ref).collect::<Vec<&str>>();
+        let is_match = |label: &str| match self {
+            Label(l) => label == l.as_str(),
+            Pattern(p) => glob_matches(p, label),
+            _ => aliases.contains(&label),
+        };
+        let mut found = labels
+            .iter()
+            .enumerate()
+            .filter(|(_, label)| is_match(label));
+        match (found.next(), found.next()) {
+            (None, _) if matches!(self, Pattern(_)) => {
+                bail!("Could not find {self:?} among {labels:?}")
+            }
+            (None, _) => fuzzy::choose(self.query(), &labels),
+            (Some((_, first)), Some((_, second))) => bail!(
+                "{self:?} is ambiguous; it matches {first:?}, {second:?}{}",
+                found
+                    .map(|(_, label)| format!(", {label:?}"))
+                    .collect::<String>()
+            ),
+            (Some((i, _)), None) => Ok(i),
+        }
+    }
+
+    // What to look for fuzzily when no label matches exactly.
+    fn query(&self) -> &str {
         use Race::*;
 
-        match s {
-            "full" => Ok(Full),
-            "half" => Ok(Half),
-            "relay" => Ok(Relay),
-            "10k" => Ok(TenK),
-            "5k" => Ok(FiveK),
-            "handcycle" => Ok(Handcycle),
-            "10kruck" => Ok(TenKRuck),
-            "solo-civilian-male-heavy" => Ok(SoloMaleCivilianHeavy),
-            "solo-military-male-heavy" => Ok(SoloMaleMilitaryHeavy),
-            "solo-military-female-heavy" => Ok(SoloFemaleMilitaryHeavy),
-            "solo-rotc-male-heavy" => Ok(SoloMaleROTCHeavy),
-            _ => Err(ParseRaceError),
+        match self {
+            Full => "marathon",
+            Half => "half marathon",
+            Relay => "relay",
+            TenK => "10k",
+            FiveK => "5k",
+            Handcycle => "handcycle",
+            TenKRuck => "10k ruck",
+            Label(s) | Pattern(s) => s,
+        }
+    }
+
+    /// Relays are scraped as teams.  A race chosen by label is taken to be
+    /// a relay if its label says so.
+    pub(crate) fn is_relay(&self) -> bool {
+        use Race::*;
+
+        match self {
+            Relay => true,
+            Label(s) | Pattern(s) => s.to_lowercase().contains("relay"),
+            _ => false,
         }
     }
 }
 
+// Case-insensitive match where * matches any run of characters and ?
+// matches exactly one.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, star_t)) => {
+                    p = star + 1;
+                    t = star_t + 1;
+                    backtrack = Some((star, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub(crate) struct Year(u16);
 
@@ -193,6 +492,27 @@ impl FromStr for Year {
     }
 }
 
+/// The browser window's size in pixels, e.g. "1920x1080".
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct WindowSize {
+    width: u32,
+    height: u32,
+}
+
+impl FromStr for WindowSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s
+            .split_once('x')
+            .map(|(w, h)| (w.trim().parse(), h.trim().parse()))
+        {
+            Some((Ok(width), Ok(height))) => Ok(Self { width, height }),
+            _ => bail!("{s:?} isn't a width and height, e.g. 1920x1080"),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, ValueEnum)]
 pub enum Event {
     Shiprock,
@@ -208,6 +528,16 @@ pub enum Event {
     BMDM,
     RioGrande,
     DoggieDash,
+    Athlinks,
+    Chronotrack,
+    ItsYourRace,
+    UltraSignup,
+    RunSignup,
+    RaceResult,
+    Webscorer,
+    RaceRoster,
+    OpenSplitTime,
+    Sporthive,
 }
 
 #[derive(Debug)]
@@ -215,7 +545,7 @@ pub struct ParseEventError;
 
 impl Display for ParseEventError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "choose \"shiprock\", \"rftz\", \"lt100\", \"moab240\", \"dptr\", \"bosque\", \"dcm\", \"ditch\", \"koth\", \"bmdm\", \"riogrande\" or \"doggie-dash\"")
+        write!(f, "choose \"shiprock\", \"rftz\", \"lt100\", \"moab240\", \"dptr\", \"bosque\", \"dcm\", \"ditch\", \"koth\", \"bmdm\", \"riogrande\", \"doggie-dash\", \"athlinks\", \"chronotrack\", \"itsyourrace\", \"ultrasignup\", \"runsignup\", \"raceresult\", \"webscorer\", \"raceroster\", \"opensplittime\" or \"sporthive\"")
     }
 }
 
@@ -239,6 +569,16 @@ impl FromStr for Event {
             "bmdm" => Ok(BMDM),
             "riogrande" => Ok(RioGrande),
             "doggie" => Ok(DoggieDash),
+            "athlinks" => Ok(Athlinks),
+            "chronotrack" => Ok(Chronotrack),
+            "itsyourrace" => Ok(ItsYourRace),
+            "ultrasignup" => Ok(UltraSignup),
+            "runsignup" => Ok(RunSignup),
+            "raceresult" => Ok(RaceResult),
+            "webscorer" => Ok(Webscorer),
+            "raceroster" => Ok(RaceRoster),
+            "opensplittime" => Ok(OpenSplitTime),
+            "sporthive" => Ok(Sporthive),
             _ => Err(ParseEventError),
         }
     }
@@ -250,8 +590,17 @@ trait Scraper {
     async fn doit(&self, client: &Client) -> AResult<()>;
 }
 
+/// How long ClientExt::wait_for waits, if --wait-timeout says otherwise
+/// than fantoccini's default.
+static WAIT_TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
 pub(crate) trait ClientExt {
     async fn pause(&self, duration: std::time::Duration) -> AResult<()>;
+    /// Waits for an element to appear, for up to --wait-timeout.
+    async fn wait_for(&self, locator: Locator<'_>) -> Result<Element, CmdError>;
+    /// Fetches JSON from within the page, so that it's requested the same
+    /// way the page's own scripts request it.
+    async fn fetch_json(&self, url: &str) -> AResult<value::Value>;
 }
 
 impl ClientExt for Client {
@@ -262,4 +611,23 @@ impl ClientExt for Client {
         .await
         .map_err(Into::into)
     }
+
+    async fn wait_for(&self, locator: Locator<'_>) -> Result<Element, CmdError> {
+        let wait = self.wait();
+        match WAIT_TIMEOUT.get() {
+            Some(&timeout) => wait.at_most(timeout),
+            None => wait,
+        }
+        .for_element(locator)
+        .await
+    }
+
+    async fn fetch_json(&self, url: &str) -> AResult<value::Value> {
+        self.execute(
+            "return fetch(arguments[0]).then(response => response.json())",
+            vec![url.into()],
+        )
+        .await
+        .with_context(|| format!("fetching {url}"))
+    }
 }
@@ -0,0 +1,194 @@
+// OpenSplitTime's spread view of an event has a row per runner and a
+// column per aid station, each with the time the runner came in and, for
+// stations where in and out are both recorded, the time they left.  The
+// times are elapsed from the start, which is what the spread shows with
+// display_style=elapsed.
+
+use {
+    crate::{
+        retry::Retry,
+        row::{headings_and_rows, Row},
+        ClientExt, Event, Opt, Scraper,
+    },
+    anyhow::{bail, Result as AResult},
+    async_trait::async_trait,
+    digital_duration_nom::duration::Duration,
+    fantoccini::{Client, Locator::Css},
+    serde::Serialize,
+    std::num::NonZeroU16,
+    tracing::warn,
+};
+
+const SPREAD_CSS: &str = "table.table";
+
+// The spread's columns that aren't aid stations.
+const RUNNER_COLUMNS: [&str; 8] = [
+    "O/G", "Rank", "Bib", "Name", "Category", "From", "Status", "Total",
+];
+
+pub struct Params {
+    // e.g. "moab-240-2023"; the slug names the year, so the year isn't used.
+    event: String,
+    retry: Retry,
+}
+
+impl Params {
+    pub(crate) fn new(opt: Opt) -> AResult<Self> {
+        use Event::*;
+
+        match opt.event {
+            OpenSplitTime => Ok(Self {
+                retry: Retry::new(&opt),
+                event: opt.id()?,
+            }),
+            _ => bail!("{:?} is not OpenSplitTime", opt.event),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+enum Status {
+    Finished,
+    Dropped,
+    NotStarted,
+    InProgress,
+}
+
+impl Status {
+    // As the spread's Status column shows it, e.g. "Not Started".
+    fn parse(status: &str) -> Option<Self> {
+        use Status::*;
+
+        match status.to_lowercase().as_str() {
+            "finished" => Some(Finished),
+            "dropped" | "dnf" => Some(Dropped),
+            "not started" | "dns" => Some(NotStarted),
+            "in progress" => Some(InProgress),
+            _ => None,
+        }
+    }
+}
+
+/// A runner's times at each aid station they reached, in course order.
+#[derive(Debug, Serialize)]
+struct Effort {
+    rank: Option<NonZeroU16>,
+    name: String,
+    bib: Option<String>,
+    category: Option<String>,
+    hometown: Option<String>,
+    stations: Vec<StationTimes>,
+    status: Status,
+    dropped_at: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct StationTimes {
+    station: String,
+    time_in: Option<Duration>,
+    time_out: Option<Duration>,
+}
+
+impl Effort {
+    // The status is the spread's own when it has one.  Otherwise, the last
+    // station is the finish, so a runner with a time there finished, and
+    // one without dropped at the last station they reached.
+    fn new(row: &Row, columns: &[(usize, &str)]) -> AResult<Self> {
+        use Status::*;
+
+        let stations = columns
+            .iter()
+            .filter_map(|&(i, station)| {
+                let (time_in, time_out) = in_and_out(row.cells.get(i)?);
+                (time_in.is_some() || time_out.is_some()).then(|| StationTimes {
+                    station: station.to_string(),
+                    time_in,
+                    time_out,
+                })
+            })
+            .collect::<Vec<_>>();
+        let finish = columns.last().map(|&(_, station)| station);
+        let status = row
+            .get(&["Status"])
+            .and_then(Status::parse)
+            .unwrap_or_else(|| match stations.last() {
+                None => NotStarted,
+                Some(last) if Some(last.station.as_str()) == finish => Finished,
+                Some(_) => Dropped,
+            });
+        let dropped_at = match status {
+            Dropped => stations.last().map(|last| last.station.clone()),
+            _ => None,
+        };
+        Ok(Self {
+            rank: row.get(&["O/G", "Rank"]).and_then(overall_rank),
+            name: row.required(&["Name"])?,
+            bib: row.optional(&["Bib"]),
+            category: row.optional(&["Category"]),
+            hometown: row.optional(&["From"]),
+            stations,
+            status,
+            dropped_at,
+        })
+    }
+}
+
+fn is_runner_column(heading: &str) -> bool {
+    RUNNER_COLUMNS
+        .iter()
+        .any(|column| heading.trim().eq_ignore_ascii_case(column))
+}
+
+// "O/G" is the overall rank and the gender rank, e.g. "12 / 3".
+fn overall_rank(o_g: &str) -> Option<NonZeroU16> {
+    o_g.split('/').next()?.trim().parse().ok()
+}
+
+// A station's cell has one time, or an in time and an out time, with "--"
+// for times that weren't recorded.
+fn in_and_out(cell: &str) -> (Option<Duration>, Option<Duration>) {
+    let mut times = cell
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.parse().ok());
+    (times.next().flatten(), times.next().flatten())
+}
+
+#[async_trait]
+impl Scraper for Params {
+    fn url(&self) -> String {
+        format!(
+            "https://www.opensplittime.org/events/{}/spread?display_style=elapsed",
+            self.event
+        )
+    }
+
+    async fn doit(&self, client: &Client) -> AResult<()> {
+        let (headings, rows) = self
+            .retry
+            .run(client, || async {
+                let table = client.wait_for(Css(SPREAD_CSS)).await?;
+                headings_and_rows(&table).await
+            })
+            .await?;
+        let stations = headings
+            .iter()
+            .enumerate()
+            .filter(|(_, heading)| !is_runner_column(heading))
+            .map(|(i, heading)| (i, heading.trim()))
+            .collect::<Vec<_>>();
+        let mut efforts = Vec::new();
+        for cells in rows {
+            let row = Row {
+                headings: &headings,
+                cells: &cells,
+            };
+            match Effort::new(&row, &stations) {
+                Ok(effort) => efforts.push(effort),
+                Err(e) => warn!(?cells, error = ?e, "discarding row"),
+            }
+        }
+        println!("{}", serde_json::to_string(&efforts).unwrap());
+        Ok(())
+    }
+}
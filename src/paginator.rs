@@ -0,0 +1,417 @@
+// Scraping results that are spread across pages: parse the page, print
+// what was parsed, go to the next page and repeat until there is no next
+// page.  Sites differ only in how the next page's control is found, in
+// how they show that there isn't a next page and in what changes when
+// the next page replaces the old one.  Sites whose pages are read by
+// number (by URL or offset) have no control at all; for them, the page
+// after the last is empty.
+
+use {
+    crate::{dedup::Dedup, progress::Progress, retry::Retry, ClientExt, Opt},
+    anyhow::{bail, Result as AResult},
+    fantoccini::{
+        elements::Element,
+        error::{
+            CmdError,
+            ErrorStatus::{NoSuchElement, StaleElementReference},
+            WebDriver,
+        },
+        Client,
+        Locator::Css,
+    },
+    serde::Serialize,
+    std::{
+        future::Future,
+        num::NonZeroU16,
+        time::{Duration, Instant},
+    },
+    tracing::{debug, info, info_span, warn, Instrument},
+};
+
+/// How long a page has to change after its next page's control is clicked.
+const CHANGE_TIMEOUT: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How to find the control that goes to the next page.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum NextPage {
+    /// The last element matching the selector is the next button as long
+    /// as its html is `html` (e.g. Athlinks' pager, whose last button is
+    /// "&gt;" on every page but the last).
+    LastWithHtml {
+        css: &'static str,
+        html: &'static str,
+    },
+    /// The element is on every page, but disabled on the last, either by
+    /// its disabled attribute or by a class with "disabled" in it.
+    UnlessDisabled(&'static str),
+    /// The element is missing on the last page.
+    UnlessMissing(&'static str),
+}
+
+impl NextPage {
+    async fn find(self, c: &Client) -> AResult<Option<Element>> {
+        use NextPage::*;
+
+        match self {
+            LastWithHtml { css, html } => {
+                let last = c.find_all(Css(css)).await?.pop();
+                match last {
+                    Some(e) if e.html(true).await? == html => Ok(Some(e)),
+                    _ => Ok(None),
+                }
+            }
+            UnlessDisabled(css) => {
+                let e = c.find(Css(css)).await?;
+                let disabled = e.attr("disabled").await?.is_some()
+                    || e.attr("class")
+                        .await?
+                        .is_some_and(|class| class.contains("disabled"));
+                Ok((!disabled).then_some(e))
+            }
+            UnlessMissing(css) => match c.find(Css(css)).await {
+                Ok(e) => Ok(Some(e)),
+                Err(CmdError::Standard(WebDriver {
+                    error: NoSuchElement,
+                    ..
+                })) => Ok(None),
+                Err(err) => bail!(err),
+            },
+        }
+    }
+}
+
+/// The elements that are replaced, or whose text changes, when a new page
+/// replaces the old (e.g. the rows of results), so that what's read after
+/// clicking to the next page is the next page.  The first of them may not
+/// change (e.g. a heading row), so it's only a sign of a new page if it's
+/// been replaced; otherwise, all of their text is compared.
+pub(crate) struct Marker {
+    css: String,
+    first: Element,
+    text: String,
+}
+
+impl Marker {
+    pub(crate) async fn find(c: &Client, css: &str) -> AResult<Option<Self>> {
+        match c.find(Css(css)).await {
+            Ok(first) => Ok(Some(Self {
+                css: css.to_string(),
+                first,
+                text: raw_text(c, css).await?,
+            })),
+            Err(CmdError::Standard(WebDriver {
+                error: NoSuchElement,
+                ..
+            })) => Ok(None),
+            Err(err) => bail!(err),
+        }
+    }
+
+    /// Waits for the marker's page to be replaced, returning false if it
+    /// isn't within CHANGE_TIMEOUT.
+    pub(crate) async fn replaced(&self, c: &Client) -> AResult<bool> {
+        let start = Instant::now();
+        loop {
+            match self.first.tag_name().await {
+                Ok(_) => {}
+                Err(CmdError::Standard(WebDriver {
+                    error: StaleElementReference,
+                    ..
+                })) => return Ok(true),
+                Err(err) => bail!(err),
+            }
+            if raw_text(c, &self.css).await? != self.text {
+                return Ok(true);
+            }
+            if start.elapsed() >= CHANGE_TIMEOUT {
+                return Ok(false);
+            }
+            c.pause(POLL_INTERVAL).await?;
+        }
+    }
+}
+
+/// How one page of results leads to the next.
+#[derive(Clone, Copy, Debug)]
+enum Pages {
+    /// There's no next page to click to: the results are on one page, or
+    /// each page is read by its number with paginate_numbered.
+    Unclicked,
+    /// Clicking `next` replaces the page, including the rows `marker`
+    /// selects.
+    Clicked {
+        next: NextPage,
+        marker: &'static str,
+    },
+}
+
+pub(crate) struct Paginator {
+    pages: Pages,
+    delay: Duration,
+    max_pages: Option<NonZeroU16>,
+    keep_duplicates: bool,
+    retry: Retry,
+    total: Option<&'static str>,
+}
+
+impl Paginator {
+    /// `marker` is the selector of the page's rows, whose text is how a
+    /// page is told from the one before it.
+    pub(crate) fn new(opt: &Opt, next: NextPage, marker: &'static str) -> Self {
+        Self::with_pages(opt, Pages::Clicked { next, marker })
+    }
+
+    /// For results without a next page to click to.
+    pub(crate) fn unclicked(opt: &Opt) -> Self {
+        Self::with_pages(opt, Pages::Unclicked)
+    }
+
+    fn with_pages(opt: &Opt, pages: Pages) -> Self {
+        Self {
+            pages,
+            delay: opt.page_delay.map_or(Duration::ZERO, Duration::from_millis),
+            max_pages: opt.max_pages,
+            keep_duplicates: opt.keep_duplicates,
+            retry: Retry::new(opt),
+            total: None,
+        }
+    }
+
+    /// `css` is the selector of where the page shows how many results
+    /// there are, e.g. "Showing 1 to 100 of 1,234 entries".
+    pub(crate) fn with_total(self, css: &'static str) -> Self {
+        Self {
+            total: Some(css),
+            ..self
+        }
+    }
+
+    /// Prints the results `page` parses from each page as a JSON array,
+    /// clicking from each page to the next until there isn't one.
+    /// Finishers already printed from an earlier page aren't printed again.
+    pub(crate) async fn paginate<T, F, Fut>(&self, c: &Client, mut page: F) -> AResult<()>
+    where
+        T: Serialize,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = AResult<Vec<T>>>,
+    {
+        let mut printer = Printer::new(self);
+        for number in 1.. {
+            if number > 1 {
+                let Pages::Clicked { next, marker } = self.pages else {
+                    break;
+                };
+                if !self.next_page(c, next, marker, number - 1).await? {
+                    break;
+                }
+                self.pause(c).await?;
+            }
+            let span = info_span!("page", number);
+            let rows = self
+                .retry
+                .run(c, &mut page)
+                .instrument(span.clone())
+                .await?;
+            // The rows as they are on the page, since two pages can have
+            // the same rows once rows are discarded (e.g. none).
+            let seen = match self.pages {
+                Pages::Clicked { marker, .. } => raw_text(c, marker).await?,
+                Pages::Unclicked => String::new(),
+            };
+            if !printer
+                .print(c, number, rows, seen)
+                .instrument(span)
+                .await?
+            {
+                break;
+            }
+        }
+        printer.dedup.report();
+        Ok(())
+    }
+
+    /// Like paginate, but for results whose pages are read by number (e.g.
+    /// with the number in the URL), which `page` does.  It returns the
+    /// page's results along with the page as read, before any results are
+    /// discarded (e.g. its JSON), or None for the empty page after the
+    /// last.
+    pub(crate) async fn paginate_numbered<T, F, Fut>(&self, c: &Client, mut page: F) -> AResult<()>
+    where
+        T: Serialize,
+        F: FnMut(u16) -> Fut,
+        Fut: Future<Output = AResult<Option<(Vec<T>, String)>>>,
+    {
+        let mut printer = Printer::new(self);
+        for number in 1.. {
+            if number > 1 {
+                self.pause(c).await?;
+            }
+            let span = info_span!("page", number);
+            let read = self
+                .retry
+                .run(c, || page(number))
+                .instrument(span.clone())
+                .await?;
+            let (rows, seen) = match read {
+                None => break,
+                Some(read) => read,
+            };
+            if !printer
+                .print(c, number, rows, seen)
+                .instrument(span)
+                .await?
+            {
+                break;
+            }
+        }
+        printer.dedup.report();
+        Ok(())
+    }
+
+    // Returns whether there's a next page, in which case it's replaced
+    // the page `from`.
+    async fn next_page(
+        &self,
+        c: &Client,
+        next: NextPage,
+        marker: &str,
+        from: u16,
+    ) -> AResult<bool> {
+        let marker = Marker::find(c, marker).await?;
+        // The button is found again for each click, since a click that
+        // fails may be because it's been redrawn.
+        let clicked = self
+            .retry
+            .run(c, || async {
+                match next.find(c).await? {
+                    None => Ok(false),
+                    Some(button) => {
+                        button.click().await?;
+                        Ok(true)
+                    }
+                }
+            })
+            .instrument(info_span!("next page", from))
+            .await?;
+        if !clicked {
+            return Ok(false);
+        }
+        if let Some(marker) = marker {
+            if !marker.replaced(c).await? {
+                warn!(
+                    page = from,
+                    timeout = ?CHANGE_TIMEOUT,
+                    "didn't change after clicking next"
+                );
+            }
+        }
+        Ok(true)
+    }
+
+    async fn pause(&self, c: &Client) -> AResult<()> {
+        if !self.delay.is_zero() {
+            c.pause(self.delay).await?;
+        }
+        Ok(())
+    }
+}
+
+// The text of every element `css` selects, read in one round trip rather
+// than one per element.
+async fn raw_text(c: &Client, css: &str) -> AResult<String> {
+    let text = c
+        .execute(
+            "return Array.from(document.querySelectorAll(arguments[0]), e => e.textContent).join('\\n')",
+            vec![css.into()],
+        )
+        .await?;
+    Ok(text.as_str().unwrap_or_default().to_string())
+}
+
+/// What's kept from page to page while printing them.
+struct Printer<'a> {
+    paginator: &'a Paginator,
+    dedup: Dedup,
+    progress: Progress,
+    repeats: Repeats,
+}
+
+impl<'a> Printer<'a> {
+    fn new(paginator: &'a Paginator) -> Self {
+        Self {
+            paginator,
+            dedup: Dedup::new(paginator.keep_duplicates),
+            progress: Progress::new(),
+            repeats: Repeats::default(),
+        }
+    }
+
+    // Prints the page's rows, unless the page is the same as the one
+    // before it.  Returns whether to go on to the next page.
+    async fn print<T: Serialize>(
+        &mut self,
+        c: &Client,
+        number: u16,
+        rows: Vec<T>,
+        seen: String,
+    ) -> AResult<bool> {
+        debug!(rows = rows.len(), "read");
+        if self.repeats.is_repeat(seen) {
+            info!("same as the page before it; stopping");
+            return Ok(false);
+        }
+        if let Some(total) = self.paginator.total {
+            self.progress.read_total(c, total).await;
+        }
+        let rows = self.dedup.retain(rows);
+        self.progress.page(rows.len());
+        if !rows.is_empty() {
+            println!("{}", serde_json::to_string(&rows).unwrap());
+        }
+        Ok(!self
+            .paginator
+            .max_pages
+            .is_some_and(|max| number >= max.get()))
+    }
+}
+
+/// Tells when a page is the same as the one before it, meaning that going
+/// to the next page didn't lead anywhere new, so that rather than loop
+/// forever, that's the end.  Pages are compared as they were read, before
+/// any rows were discarded, since pages whose rows are all discarded
+/// aren't the same page.
+#[derive(Default)]
+struct Repeats {
+    previous: Option<String>,
+}
+
+impl Repeats {
+    fn is_repeat(&mut self, seen: String) -> bool {
+        let repeat = self.previous.as_ref() == Some(&seen);
+        self.previous = Some(seen);
+        repeat
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_same_page_twice_is_a_repeat() {
+        let mut repeats = Repeats::default();
+        assert!(!repeats.is_repeat("1 Jane Doe".to_string()));
+        assert!(repeats.is_repeat("1 Jane Doe".to_string()));
+    }
+
+    #[test]
+    fn different_pages_are_not_repeats_even_if_nothing_on_them_is_kept() {
+        // e.g. two pages of participants in other races, which both
+        // parse to [].
+        let mut repeats = Repeats::default();
+        assert!(!repeats.is_repeat("1 Jane Doe 50K".to_string()));
+        assert!(!repeats.is_repeat("2 John Roe 50K".to_string()));
+        assert!(!repeats.is_repeat("1 Jane Doe 50K".to_string()));
+    }
+}
@@ -0,0 +1,320 @@
+// The result record the scrapers for platforms that aren't tied to a
+// single event emit, so that results from different sites can be
+// consumed by the same code.  Fields a site doesn't show are null.
+
+use {
+    crate::{progress, row::Row, times::Times},
+    anyhow::{bail, Result as AResult},
+    digital_duration_nom::duration::Duration,
+    serde::Serialize,
+    std::num::{NonZeroU16, NonZeroU8},
+    tracing::warn,
+};
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Placement {
+    pub rank: Option<NonZeroU16>,
+    pub name: String,
+    pub bib: Option<String>,
+    pub gender: Option<String>,
+    pub age: Option<NonZeroU8>,
+    pub division: Option<String>,
+    pub division_rank: Option<NonZeroU16>,
+    pub hometown: Option<String>,
+    #[serde(flatten)]
+    pub times: Times,
+    pub status: Status,
+    pub pace: Option<Duration>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub splits: Vec<Split>,
+}
+
+/// A team's row, from sites that rank relay and team entries separately.
+#[derive(Debug, Serialize)]
+pub(crate) struct TeamPlacement {
+    pub rank: Option<NonZeroU16>,
+    pub team_name: String,
+    pub division: Option<String>,
+    #[serde(flatten)]
+    pub times: Times,
+    pub status: Status,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub splits: Vec<Split>,
+}
+
+/// The time of a split, lap or leg, named by its column's heading.
+#[derive(Debug, Serialize)]
+pub(crate) struct Split {
+    pub split: String,
+    pub time: Duration,
+}
+
+/// Whether a placement finished.  Those who didn't are listed without a
+/// time.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub(crate) enum Status {
+    #[default]
+    Finished,
+    DidNotFinish,
+    DidNotStart,
+    Disqualified,
+}
+
+impl Status {
+    // As a Status column shows it, or a time column in place of a time.
+    fn parse(status: &str) -> Option<Self> {
+        use Status::*;
+
+        match status.to_lowercase().as_str() {
+            "finished" => Some(Finished),
+            "dnf" | "did not finish" => Some(DidNotFinish),
+            "dns" | "did not start" => Some(DidNotStart),
+            "dq" | "dsq" | "disqualified" => Some(Disqualified),
+            _ => None,
+        }
+    }
+}
+
+const TIME_COLUMNS: [&str; 4] = ["Time", "Finish Time", "Official Time", "Finish"];
+const CHIP_TIME_COLUMNS: [&str; 4] = ["Chip Time", "Chip", "Net Time", "Net"];
+const GUN_TIME_COLUMNS: [&str; 4] = ["Gun Time", "Gun", "Clock Time", "Clock"];
+const RANK_COLUMNS: [&str; 5] = ["Place", "Overall Place", "Overall", "Pl", "Rank"];
+const DIVISION_COLUMNS: [&str; 5] = ["Division", "Age Group", "Category", "Div", "AG"];
+
+impl Placement {
+    /// Reads a placement from a results table row, using the headings the
+    /// sites we scrape give their columns.  A row without a name, or
+    /// without a time when it doesn't say why (e.g. "DNF"), is an error, so
+    /// that the caller can report it.
+    pub(crate) fn from_row(row: &Row) -> AResult<Self> {
+        let (times, status) = times(row)?;
+        Ok(Self {
+            rank: rank(row, &RANK_COLUMNS),
+            name: name(row)?,
+            bib: row.optional(&["Bib", "Bib #", "Bib No", "No."]),
+            gender: row.optional(&["Gender", "Sex", "G"]),
+            age: row.parsed(&["Age"]),
+            division: row.optional(&DIVISION_COLUMNS),
+            division_rank: rank(
+                row,
+                &[
+                    "Division Place",
+                    "Div Place",
+                    "Category Place",
+                    "AG Place",
+                    "Div Pl",
+                ],
+            ),
+            hometown: hometown(row),
+            times,
+            status,
+            pace: row.get(&["Pace", "Avg Pace", "Min/Mile"]).and_then(pace),
+            splits: splits(row),
+        })
+    }
+
+    /// Reads a placement from each of a table's rows.  The rows that aren't
+    /// placements are reported and skipped.
+    pub(crate) fn from_rows(headings: &[String], rows: &[Vec<String>]) -> Vec<Self> {
+        from_rows(headings, rows, Self::from_row)
+    }
+}
+
+impl TeamPlacement {
+    pub(crate) fn from_row(row: &Row) -> AResult<Self> {
+        let members = row
+            .get(&["Members", "Team Members", "Runners"])
+            .map(|members| {
+                members
+                    .split([',', '/', ';'])
+                    .map(str::trim)
+                    .filter(|member| !member.is_empty())
+                    .map(ToString::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let (times, status) = times(row)?;
+        Ok(Self {
+            rank: rank(row, &RANK_COLUMNS),
+            team_name: row.required(&["Team Name", "Team"])?,
+            division: row.optional(&DIVISION_COLUMNS),
+            times,
+            status,
+            members,
+            splits: splits(row),
+        })
+    }
+
+    pub(crate) fn from_rows(headings: &[String], rows: &[Vec<String>]) -> Vec<Self> {
+        from_rows(headings, rows, Self::from_row)
+    }
+}
+
+fn from_rows<T>(
+    headings: &[String],
+    rows: &[Vec<String>],
+    from_row: fn(&Row) -> AResult<T>,
+) -> Vec<T> {
+    rows.iter()
+        .filter_map(|cells| {
+            from_row(&Row { headings, cells })
+                .inspect_err(|e| {
+                    progress::discarded();
+                    warn!(?cells, error = ?e, "discarding row")
+                })
+                .ok()
+        })
+        .collect()
+}
+
+// The status is from the Status column when there is one, and otherwise
+// from a time column that says e.g. "DNF" instead of a time.
+fn times(row: &Row) -> AResult<(Times, Status)> {
+    let status = row.get(&["Status"]).and_then(Status::parse).or_else(|| {
+        [&TIME_COLUMNS, &CHIP_TIME_COLUMNS, &GUN_TIME_COLUMNS]
+            .iter()
+            .find_map(|names| row.get(&names[..]).and_then(Status::parse))
+    });
+    let times = Times::new(
+        row.parsed(&TIME_COLUMNS),
+        row.parsed(&CHIP_TIME_COLUMNS),
+        row.parsed(&GUN_TIME_COLUMNS),
+    );
+    match (times, status) {
+        (Some(times), status) => Ok((times, status.unwrap_or_default())),
+        (None, Some(status)) if status != Status::Finished => Ok((Times::unknown(None), status)),
+        (None, _) => bail!("no time in {:?}", row.cells),
+    }
+}
+
+// Split columns are headed e.g. "Split 1", "Lap 2" or "Leg 3 Time" (but
+// not "Laps", which is a count).  Splits that weren't timed are blank and
+// are left out.
+fn splits(row: &Row) -> Vec<Split> {
+    const PREFIXES: [&str; 4] = ["split", "lap", "leg", "segment"];
+
+    row.headings
+        .iter()
+        .zip(row.cells)
+        .filter(|(heading, _)| {
+            let heading = heading.trim().to_lowercase();
+            PREFIXES
+                .iter()
+                .any(|prefix| match heading.strip_prefix(prefix) {
+                    Some(rest) => rest.starts_with(|c: char| c == ' ' || c.is_ascii_digit()),
+                    None => false,
+                })
+        })
+        .filter_map(|(heading, cell)| {
+            cell.trim().parse().ok().map(|time| Split {
+                split: heading.trim().to_string(),
+                time,
+            })
+        })
+        .collect()
+}
+
+// Places are sometimes shown as "1." or "1st".
+fn rank(row: &Row, names: &[&str]) -> Option<NonZeroU16> {
+    let rank = row.get(names)?;
+    let digits = rank
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rank.len());
+    rank[..digits].parse().ok()
+}
+
+fn name(row: &Row) -> AResult<String> {
+    match row.optional(&["Name", "Participant", "Runner", "Athlete"]) {
+        Some(name) => Ok(name),
+        None => Ok(format!(
+            "{} {}",
+            row.required(&["First Name", "First"])?,
+            row.required(&["Last Name", "Last"])?
+        )),
+    }
+}
+
+fn hometown(row: &Row) -> Option<String> {
+    match (
+        row.get(&["Hometown", "City", "Location", "From"]),
+        row.get(&["State", "ST"]),
+    ) {
+        (Some(city), Some(state)) => Some(format!("{city}, {state}")),
+        (city, state) => city.or(state).map(ToString::to_string),
+    }
+}
+
+// "7:32/mi", "7:32 min/mile" and "7:32" are all 7:32.
+fn pace(pace: &str) -> Option<Duration> {
+    pace.split(['/', ' ']).next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{row::fixture, times::Clock},
+    };
+
+    fn duration(s: &str) -> Duration {
+        s.parse().unwrap()
+    }
+
+    fn placements() -> Vec<AResult<Placement>> {
+        let (headings, rows) = fixture(include_str!("../tests/fixtures/runsignup.json"));
+        rows.iter()
+            .map(|cells| {
+                Placement::from_row(&Row {
+                    headings: &headings,
+                    cells,
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_every_column() {
+        let placement = placements().remove(0).unwrap();
+        assert_eq!(placement.rank, NonZeroU16::new(1));
+        assert_eq!(placement.name, "Jane Doe");
+        assert_eq!(placement.bib.as_deref(), Some("101"));
+        assert_eq!(placement.gender.as_deref(), Some("F"));
+        assert_eq!(placement.age, NonZeroU8::new(34));
+        assert_eq!(placement.hometown.as_deref(), Some("Albuquerque, NM"));
+        assert_eq!(placement.times.time, Some(duration("1:23:45")));
+        assert_eq!(placement.times.time_is, Clock::Chip);
+        assert_eq!(placement.times.gun_time, Some(duration("1:24:02")));
+        assert_eq!(placement.status, Status::Finished);
+        assert_eq!(placement.pace, Some(duration("6:24")));
+        assert_eq!(placement.splits.len(), 1);
+        assert_eq!(placement.splits[0].split, "Split 1");
+        assert_eq!(placement.splits[0].time, duration("41:10"));
+    }
+
+    #[test]
+    fn tolerates_decorated_ranks_and_blank_cells() {
+        let placement = placements().remove(1).unwrap();
+        assert_eq!(placement.rank, NonZeroU16::new(2));
+        assert_eq!(placement.hometown.as_deref(), Some("Santa Fe"));
+        assert!(placement.splits.is_empty());
+    }
+
+    #[test]
+    fn keeps_dnfs_without_a_time() {
+        let placement = placements().remove(2).unwrap();
+        assert_eq!(placement.name, "Sam Poe");
+        assert_eq!(placement.rank, None);
+        assert_eq!(placement.times.time, None);
+        assert_eq!(placement.status, Status::DidNotFinish);
+    }
+
+    #[test]
+    fn rejects_malformed_rows() {
+        // A row without a name and a time that isn't one.
+        for (i, placement) in placements().iter().enumerate().skip(3) {
+            assert!(placement.is_err(), "row {i} was read");
+        }
+    }
+}
@@ -0,0 +1,89 @@
+// Long scrapes (e.g. the Duke City Marathon's) take minutes, so how far
+// along they are is logged after each page.  Like the rest of the logging,
+// it goes to stderr, leaving stdout to the results.
+
+use {
+    fantoccini::{Client, Locator::Css},
+    std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::{Duration, Instant},
+    },
+    tracing::info,
+};
+
+// Rows are discarded deep within each site's parsing, so rather than
+// thread a count through all of it, they're counted here.
+static DISCARDED: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts a row that couldn't be parsed.
+pub(crate) fn discarded() {
+    DISCARDED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) struct Progress {
+    start: Instant,
+    pages: usize,
+    collected: usize,
+    total: Option<usize>,
+}
+
+impl Progress {
+    pub(crate) fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            pages: 0,
+            collected: 0,
+            total: None,
+        }
+    }
+
+    /// Reads the number of results from a page that shows it (e.g.
+    /// "Showing 1 to 100 of 1,234 entries"), which makes it possible to
+    /// estimate how much longer the scrape will take.
+    pub(crate) async fn read_total(&mut self, c: &Client, css: &str) {
+        if self.total.is_some() {
+            return;
+        }
+        if let Ok(e) = c.find(Css(css)).await {
+            if let Ok(text) = e.text().await {
+                self.total = total(&text);
+            }
+        }
+    }
+
+    /// Logs the progress after a page that added `collected` results.
+    pub(crate) fn page(&mut self, collected: usize) {
+        self.pages += 1;
+        self.collected += collected;
+        let discarded = DISCARDED.load(Ordering::Relaxed);
+        let elapsed = self.start.elapsed();
+        let read = self.collected + discarded;
+        let remaining = self
+            .total
+            .filter(|_| read > 0)
+            .map(|total| elapsed.mul_f64(total.saturating_sub(read) as f64 / read as f64));
+        info!(
+            pages = self.pages,
+            collected = self.collected,
+            discarded,
+            elapsed = ?seconds(elapsed),
+            total = self.total,
+            remaining = remaining.map(seconds).map(tracing::field::debug),
+            "progress"
+        );
+    }
+}
+
+fn seconds(duration: Duration) -> Duration {
+    Duration::from_secs(duration.as_secs())
+}
+
+fn total(text: &str) -> Option<usize> {
+    text.rsplit_once(" of ")?
+        .1
+        .split_whitespace()
+        .next()?
+        .replace(',', "")
+        .parse()
+        .ok()
+}
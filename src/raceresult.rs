@@ -0,0 +1,334 @@
+// my.raceresult.com pages are rendered from JSON: a config that names the
+// event's contests and result lists, and the rows of whichever list is
+// shown.  Rather than scrape the rendered tables, the JSON is fetched.
+
+use {
+    crate::{placement::Placement, retry::Retry, ClientExt, Event, Opt, Race, Scraper},
+    anyhow::{bail, Result as AResult},
+    async_trait::async_trait,
+    fantoccini::Client,
+    serde::{Deserialize, Deserializer},
+    serde_json::Value,
+    std::collections::BTreeMap,
+    tracing::info,
+    url::Url,
+};
+
+pub struct Params {
+    event_id: u32,
+    race: Option<Race>,
+    retry: Retry,
+}
+
+impl Params {
+    // Each year of an event has its own event id, so the year isn't used.
+    pub(crate) fn new(opt: Opt) -> AResult<Self> {
+        use Event::*;
+
+        match opt.event {
+            RaceResult => Ok(Self {
+                event_id: opt.id()?,
+                retry: Retry::new(&opt),
+                race: opt.race,
+            }),
+            _ => bail!("{:?} is not RaceResult", opt.event),
+        }
+    }
+
+    fn config_url(&self) -> String {
+        format!(
+            "https://my.raceresult.com/{}/RRPublish/data/config?page=results&noVisitor=1",
+            self.event_id
+        )
+    }
+
+    fn list_url(&self, config: &Config, list: &List, contest: &str) -> AResult<String> {
+        let server = config.server.as_deref().unwrap_or("my.raceresult.com");
+        Ok(Url::parse_with_params(
+            &format!("https://{server}/{}/RRPublish/data/list", self.event_id),
+            &[
+                ("key", config.key.as_str()),
+                ("listname", list.name.as_str()),
+                ("page", "results"),
+                ("contest", contest),
+                ("r", "all"),
+                ("l", "0"),
+            ],
+        )?
+        .to_string())
+    }
+
+    // Each of a contest's lists (e.g. overall and by age group) is labeled
+    // with the contest's name and its own (e.g. "Marathon Overall
+    // Results"), so that --race can choose between the lists as well as
+    // between the contests.
+    fn list<'a>(&self, config: &'a Config) -> AResult<(&'a str, &'a List)> {
+        let choices = choices(config);
+        let labels = choices
+            .iter()
+            .map(|(_, _, label)| label.as_str())
+            .collect::<Vec<_>>();
+        let index = match &self.race {
+            None if choices.is_empty() => bail!("The event has no result lists"),
+            None if choices.len() == 1 => 0,
+            None => bail!("Choose a result list with --race; the event has {labels:?}"),
+            Some(race) => race.choose(&labels, &[])?,
+        };
+        let (contest, list, _) = choices[index];
+        Ok((contest, list))
+    }
+}
+
+// Contest "0" stands for every contest, so it's only used when an event
+// has no others, but a list for contest "0" is a list of each contest.
+fn choices(config: &Config) -> Vec<(&str, &List, String)> {
+    let mut contests = config
+        .contests
+        .iter()
+        .filter(|(id, _)| id.as_str() != "0")
+        .map(|(id, name)| (id.as_str(), name.as_str()))
+        .collect::<Vec<_>>();
+    if contests.is_empty() {
+        contests.push(("0", ""));
+    }
+    contests
+        .into_iter()
+        .flat_map(|(contest, name)| {
+            config
+                .lists
+                .iter()
+                .filter(move |list| list.contest == "0" || list.contest == contest)
+                .map(move |list| {
+                    let label = format!("{name} {}", list.label()).trim().to_string();
+                    (contest, list, label)
+                })
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    key: String,
+    server: Option<String>,
+    #[serde(default)]
+    lists: Vec<List>,
+    #[serde(default)]
+    contests: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct List {
+    // e.g. "Result Lists|Overall Results"
+    name: String,
+    #[serde(deserialize_with = "id")]
+    contest: String,
+}
+
+impl List {
+    fn label(&self) -> &str {
+        self.name.rsplit('|').next().unwrap_or(&self.name)
+    }
+}
+
+// Ids are sometimes strings and sometimes numbers.
+fn id<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+    Ok(match Value::deserialize(d)? {
+        Value::String(s) => s,
+        v => v.to_string(),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct ListData {
+    list: ListFormat,
+    #[serde(rename = "DataFields")]
+    data_fields: Vec<String>,
+    data: Value,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListFormat {
+    fields: Vec<Field>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Field {
+    expression: String,
+    #[serde(default)]
+    label: String,
+}
+
+impl ListData {
+    // Each row has a value per data field.  The fields that are shown have
+    // a label, which is what the page uses as the column's heading; the
+    // others (e.g. "BIB") are their own heading.
+    fn headings(&self) -> Vec<String> {
+        self.data_fields
+            .iter()
+            .map(|data_field| {
+                self.list
+                    .fields
+                    .iter()
+                    .find(|f| &f.expression == data_field && !f.label.is_empty())
+                    .map_or_else(|| data_field.clone(), |f| f.label.clone())
+            })
+            .collect()
+    }
+
+    fn placements(&self) -> Vec<Placement> {
+        let headings = self.headings();
+        let mut rows = Vec::new();
+        collect_rows(&self.data, &mut rows);
+        Placement::from_rows(&headings, &rows)
+    }
+}
+
+// The rows are either an array or, when the list is grouped (e.g. by
+// gender), objects keyed by group whose leaves are arrays of rows.
+fn collect_rows(data: &Value, rows: &mut Vec<Vec<String>>) {
+    match data {
+        Value::Array(items) if items.iter().all(Value::is_array) => {
+            items.iter().for_each(|item| collect_rows(item, rows))
+        }
+        Value::Array(cells) => rows.push(cells.iter().map(cell_text).collect()),
+        Value::Object(groups) => groups.values().for_each(|group| collect_rows(group, rows)),
+        _ => {}
+    }
+}
+
+fn cell_text(cell: &Value) -> String {
+    match cell {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        v => v.to_string(),
+    }
+}
+
+#[async_trait]
+impl Scraper for Params {
+    fn url(&self) -> String {
+        format!("https://my.raceresult.com/{}/results", self.event_id)
+    }
+
+    async fn doit(&self, client: &Client) -> AResult<()> {
+        let config_url = self.config_url();
+        let config = self
+            .retry
+            .run(client, || client.fetch_json(&config_url))
+            .await?;
+        let config: Config = serde_json::from_value(config)?;
+        let (contest, list) = self.list(&config)?;
+        info!(list = list.label(), contest, "scraping");
+        let url = self.list_url(&config, list, contest)?;
+        let data = self.retry.run(client, || client.fetch_json(&url)).await?;
+        let data: ListData = serde_json::from_value(data)?;
+        println!("{}", serde_json::to_string(&data.placements()).unwrap());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json};
+
+    fn list_data(data: Value) -> ListData {
+        serde_json::from_value(json!({
+            "list": {
+                "Fields": [
+                    { "Expression": "RANK", "Label": "Place" },
+                    { "Expression": "DisplayName", "Label": "Name" },
+                    { "Expression": "TIME", "Label": "Time" },
+                    { "Expression": "AGE" }
+                ]
+            },
+            "DataFields": ["BIB", "RANK", "DisplayName", "TIME", "AGE"],
+            "data": data
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn headings_are_labels_or_data_fields() {
+        let data = list_data(json!([]));
+        assert_eq!(data.headings(), ["BIB", "Place", "Name", "Time", "AGE"]);
+    }
+
+    #[test]
+    fn collects_rows_from_arrays_and_groups() {
+        let mut rows = Vec::new();
+        collect_rows(&json!([["1", 2, null]]), &mut rows);
+        assert_eq!(rows, [["1", "2", ""]]);
+
+        let mut rows = Vec::new();
+        collect_rows(
+            &json!({
+                "#1_Female": [["11", "1"]],
+                "#2_Male": { "#1_Under 40": [["12", "1"], ["13", "2"]] }
+            }),
+            &mut rows,
+        );
+        assert_eq!(rows, [["11", "1"], ["12", "1"], ["13", "2"]]);
+    }
+
+    #[test]
+    fn placements_of_a_grouped_list() {
+        let data = list_data(json!({
+            "#1_Female": [["101", "1.", "Jane Doe", "1:23:45", 34]],
+            "#2_Male": [
+                ["102", "1.", "John Roe", "1:30:00", 41],
+                ["103", "", "Sam Poe", "DNF", 29]
+            ]
+        }));
+        let placements = data.placements();
+        let names = placements
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Jane Doe", "John Roe", "Sam Poe"]);
+        assert_eq!(placements[0].bib.as_deref(), Some("101"));
+        assert_eq!(placements[1].rank, std::num::NonZeroU16::new(1));
+    }
+
+    fn config() -> Config {
+        serde_json::from_value(json!({
+            "key": "abc",
+            "contests": { "0": "All", "1": "Marathon", "2": "Half Marathon" },
+            "lists": [
+                { "Name": "Result Lists|Overall Results", "Contest": 0 },
+                { "Name": "Result Lists|Age Group Results", "Contest": "0" },
+                { "Name": "Result Lists|Finishers", "Contest": 2 }
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn params(race: Option<&str>) -> Params {
+        Params {
+            event_id: 1,
+            race: race.map(|race| race.parse().unwrap()),
+            retry: Retry::default(),
+        }
+    }
+
+    #[test]
+    fn race_chooses_the_contest_and_list() {
+        let config = config();
+        let (contest, list) = params(Some("Half Marathon Finishers"))
+            .list(&config)
+            .unwrap();
+        assert_eq!((contest, list.label()), ("2", "Finishers"));
+        let (contest, list) = params(Some("Marathon Age Group Results"))
+            .list(&config)
+            .unwrap();
+        assert_eq!((contest, list.label()), ("1", "Age Group Results"));
+    }
+
+    #[test]
+    fn several_lists_need_a_race() {
+        let err = params(None).list(&config()).unwrap_err();
+        assert!(err.to_string().starts_with("Choose a result list"));
+    }
+}
@@ -0,0 +1,91 @@
+// WebDriver steps fail now and then for reasons that have nothing to do
+// with the page being scraped: an element that's redrawn just as it's
+// read, a click that lands on a spinner, a slow navigation.  Those steps
+// are tried again after a while rather than failing the whole scrape.
+
+use {
+    crate::{ClientExt, Opt},
+    anyhow::{Error, Result as AResult},
+    fantoccini::{
+        error::{CmdError, ErrorStatus, WebDriver},
+        Client,
+    },
+    std::{future::Future, num::NonZeroU8, time::Duration},
+    tracing::warn,
+};
+
+const ATTEMPTS: NonZeroU8 = NonZeroU8::new(3).unwrap();
+const BACKOFF: Duration = Duration::from_millis(500);
+
+/// How many times to try a step, and how long to wait before trying it
+/// again the first time, which doubles each time after.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Retry {
+    attempts: NonZeroU8,
+    backoff: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            attempts: ATTEMPTS,
+            backoff: BACKOFF,
+        }
+    }
+}
+
+impl Retry {
+    pub(crate) fn new(opt: &Opt) -> Self {
+        Self {
+            attempts: opt.attempts.unwrap_or(ATTEMPTS),
+            backoff: opt.backoff.map_or(BACKOFF, Duration::from_millis),
+        }
+    }
+
+    /// Runs `step` until it succeeds, fails in a way that trying again
+    /// won't fix, or has been tried as many times as allowed.
+    pub(crate) async fn run<T, E, F, Fut>(self, c: &Client, mut step: F) -> AResult<T>
+    where
+        E: Into<Error>,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+        loop {
+            match step().await.map_err(Into::into) {
+                Err(err) if attempt < self.attempts.get() && is_transient(&err) => {
+                    warn!(
+                        attempt,
+                        attempts = self.attempts.get(),
+                        error = %err,
+                        ?backoff,
+                        "trying again"
+                    );
+                    c.pause(backoff).await?;
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+// Errors about the page itself (e.g. a race that isn't in the menu, or an
+// element that isn't there) are the same the next time, so only the
+// WebDriver errors of a page that's still settling, including an element
+// that didn't show up in time, are retried.  A step that depends on the
+// ones before it is retried along with them, on the page freshly loaded.
+fn is_transient(err: &Error) -> bool {
+    use ErrorStatus::*;
+
+    match err.downcast_ref::<CmdError>() {
+        Some(CmdError::WaitTimeout | CmdError::Lost(_)) => true,
+        Some(CmdError::Standard(WebDriver { error, .. })) => matches!(
+            error,
+            ElementClickIntercepted | ElementNotInteractable | StaleElementReference | Timeout
+        ),
+        _ => false,
+    }
+}
@@ -0,0 +1,170 @@
+// Reading results tables whose columns vary from page to page, so cells
+// are found by their column's heading rather than their position.
+
+use {
+    anyhow::{anyhow, Result as AResult},
+    fantoccini::{elements::Element, Locator::Css},
+    futures::stream::{self, StreamExt, TryStreamExt},
+    std::str::FromStr,
+};
+
+/// A row of a table, along with the table's headings.
+pub(crate) struct Row<'a> {
+    pub headings: &'a [String],
+    pub cells: &'a [String],
+}
+
+impl<'a> Row<'a> {
+    pub(crate) fn get(&self, names: &[&str]) -> Option<&'a str> {
+        self.headings
+            .iter()
+            .position(|h| names.iter().any(|n| h.trim().eq_ignore_ascii_case(n)))
+            .and_then(|i| self.cells.get(i))
+            .map(|cell| cell.trim())
+            .filter(|cell| !cell.is_empty())
+    }
+
+    pub(crate) fn optional(&self, names: &[&str]) -> Option<String> {
+        self.get(names).map(ToString::to_string)
+    }
+
+    pub(crate) fn required(&self, names: &[&str]) -> AResult<String> {
+        self.optional(names)
+            .ok_or_else(|| anyhow!("no {} in {:?}", names[0], self.cells))
+    }
+
+    pub(crate) fn parsed<T: FromStr>(&self, names: &[&str]) -> Option<T> {
+        self.get(names)?.parse().ok()
+    }
+}
+
+pub(crate) async fn texts(elements: Vec<Element>) -> AResult<Vec<String>> {
+    Ok(stream::iter(&elements)
+        .then(|e| e.text())
+        .try_collect()
+        .await?)
+}
+
+/// The table's headings and the text of each of its rows' cells.  Rows
+/// that don't have a cell for every heading (e.g. the heading row itself)
+/// are skipped.
+pub(crate) async fn headings_and_rows(table: &Element) -> AResult<(Vec<String>, Vec<Vec<String>>)> {
+    let headings = texts(table.find_all(Css("th")).await?).await?;
+    let mut rows = Vec::new();
+    for row in table.find_all(Css("tr")).await? {
+        let cells = texts(row.find_all(Css("td")).await?).await?;
+        if cells.len() >= headings.len() {
+            rows.push(cells);
+        }
+    }
+    Ok((headings, rows))
+}
+
+// Tables can also be read from a page's source, which takes one round trip
+// rather than one per cell, and which a saved page can be tested against.
+
+/// Like headings_and_rows, but for the table whose id is `id` in `html`.
+pub(crate) fn source_headings_and_rows(
+    html: &str,
+    id: &str,
+) -> Option<(Vec<String>, Vec<Vec<String>>)> {
+    let table = with_id(html, id, "</table>")?;
+    let headings = contents(table, "th");
+    let rows = table
+        .split("<tr")
+        .skip(1)
+        .map(|row| contents(row, "td"))
+        .filter(|cells| cells.len() >= headings.len())
+        .collect();
+    Some((headings, rows))
+}
+
+/// The value and text of each option of the select whose id is `id` in
+/// `html`.
+pub(crate) fn source_options(html: &str, id: &str) -> Vec<(String, String)> {
+    let select = match with_id(html, id, "</select>") {
+        None => return Vec::new(),
+        Some(select) => select,
+    };
+    select
+        .split("<option")
+        .skip(1)
+        .filter_map(|option| {
+            let (attributes, rest) = option.split_once('>')?;
+            let value = attributes.split_once("value=\"")?.1.split_once('"')?.0;
+            let text = rest.split("</option>").next()?;
+            Some((decode(value), text_of(text)))
+        })
+        .collect()
+}
+
+// The element from its id up to `end`.
+fn with_id<'a>(html: &'a str, id: &str, end: &str) -> Option<&'a str> {
+    let element = &html[html.find(&format!("id=\"{id}\""))?..];
+    Some(&element[..element.find(end)?])
+}
+
+// The text of each of the `tag` elements in `html`.
+fn contents(html: &str, tag: &str) -> Vec<String> {
+    let open = format!("<{tag}");
+    let close = format!("</{tag}>");
+    let mut texts = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        // e.g. <thead> when looking for <th>
+        if !rest.starts_with(|c: char| c == '>' || c.is_whitespace()) {
+            continue;
+        }
+        let inner = match rest.split_once('>') {
+            None => break,
+            Some((_, inner)) => inner,
+        };
+        let end = inner.find(&close).unwrap_or(inner.len());
+        texts.push(text_of(&inner[..end]));
+        rest = &inner[end..];
+    }
+    texts
+}
+
+// What the browser shows of some html: its text without the tags, with
+// entities decoded and each run of whitespace a single space.
+fn text_of(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    decode(&text)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn decode(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+#[derive(serde::Deserialize)]
+struct Fixture {
+    headings: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+/// A table saved in tests/fixtures, as headings_and_rows would read it.
+#[cfg(test)]
+pub(crate) fn fixture(json: &str) -> (Vec<String>, Vec<Vec<String>>) {
+    let Fixture { headings, rows } = serde_json::from_str(json).unwrap();
+    (headings, rows)
+}
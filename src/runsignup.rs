@@ -0,0 +1,201 @@
+use {
+    crate::{
+        paginator::Paginator,
+        placement::Placement,
+        retry::Retry,
+        row::{source_headings_and_rows, source_options},
+        ClientExt, Event, Opt, Race, Scraper, Year,
+    },
+    anyhow::{anyhow, bail, Result as AResult},
+    async_trait::async_trait,
+    fantoccini::{Client, Locator::Css},
+    tracing::instrument,
+};
+
+const RESULT_SET_ID: &str = "resultSetSelect";
+const RESULT_SET_SELECTOR: &str = "select#resultSetSelect";
+const RESULTS_ID: &str = "resultsTable";
+const RESULTS_CSS: &str = "table#resultsTable";
+const PER_PAGE: u16 = 100;
+
+pub struct Params {
+    race_id: u32,
+    race: Option<Race>,
+    year: Year,
+    paginator: Paginator,
+    retry: Retry,
+}
+
+impl Params {
+    pub(crate) fn new(opt: Opt) -> AResult<Self> {
+        use Event::*;
+
+        match opt.event {
+            RunSignup => Ok(Self {
+                race_id: opt.id()?,
+                year: opt.year(),
+                paginator: Paginator::unclicked(&opt),
+                retry: Retry::new(&opt),
+                race: opt.race,
+            }),
+            _ => bail!("{:?} is not RunSignup", opt.event),
+        }
+    }
+
+    #[instrument(skip_all, fields(year = %self.year, race = ?self.race))]
+    async fn result_set_id(&self, c: &Client) -> AResult<String> {
+        c.wait_for(Css(RESULT_SET_SELECTOR)).await?;
+        let result_sets = source_options(&c.source().await?, RESULT_SET_ID);
+        choose_result_set(result_sets, self.year, self.race.as_ref())
+    }
+
+    fn page_url(&self, result_set_id: &str, page: u16) -> String {
+        format!(
+            "{}?resultSetId={result_set_id}&page={page}&num={PER_PAGE}",
+            self.url()
+        )
+    }
+}
+
+// Every year's result sets are in the one menu, each labeled with its year
+// (e.g. "2023 Half Marathon Results"), so the year narrows them down and
+// the race chooses among what's left.  Returns the chosen one's id.
+fn choose_result_set(
+    mut result_sets: Vec<(String, String)>,
+    year: Year,
+    race: Option<&Race>,
+) -> AResult<String> {
+    let year = year.to_string();
+    result_sets.retain(|(_, label)| label.contains(&year));
+    let labels = result_sets
+        .iter()
+        .map(|(_, label)| label.as_str())
+        .collect::<Vec<_>>();
+    let index = match race {
+        None if labels.len() == 1 => 0,
+        None if labels.is_empty() => bail!("No {year} results in {RESULT_SET_SELECTOR}"),
+        None => bail!("Choose a race with --race; {year} has {labels:?}"),
+        Some(race) => race
+            .choose(&labels, &[])
+            .map_err(|e| anyhow!("{RESULT_SET_SELECTOR}: {e}"))?,
+    };
+    Ok(result_sets.swap_remove(index).0)
+}
+
+#[async_trait]
+impl Scraper for Params {
+    fn url(&self) -> String {
+        format!("https://runsignup.com/Race/Results/{}", self.race_id)
+    }
+
+    // The pages are requested by number rather than by clicking on "next",
+    // and past the last page RunSignup shows either an empty table or the
+    // last page again.
+    async fn doit(&self, client: &Client) -> AResult<()> {
+        let result_set_id = self
+            .retry
+            .run(client, || self.result_set_id(client))
+            .await?;
+        let result_set_id = &result_set_id;
+        self.paginator
+            .paginate_numbered(client, |page| async move {
+                client.goto(&self.page_url(result_set_id, page)).await?;
+                client.wait_for(Css(RESULTS_CSS)).await?;
+                let (headings, rows) =
+                    source_headings_and_rows(&client.source().await?, RESULTS_ID)
+                        .ok_or_else(|| anyhow!("No {RESULTS_CSS}"))?;
+                if rows.is_empty() {
+                    return Ok(None);
+                }
+                let seen = serde_json::to_string(&rows).unwrap();
+                Ok(Some((Placement::from_rows(&headings, &rows), seen)))
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A results page as the browser has it, with the 2023 half marathon's
+    // result set chosen.
+    const PAGE: &str = include_str!("../tests/fixtures/runsignup.html");
+
+    #[test]
+    fn reads_the_results_table() {
+        let (headings, rows) = source_headings_and_rows(PAGE, RESULTS_ID).unwrap();
+        assert_eq!(
+            headings,
+            [
+                "Place",
+                "Bib",
+                "Name",
+                "Gender",
+                "Age",
+                "City",
+                "State",
+                "Chip Time",
+                "Clock Time",
+                "Pace"
+            ]
+        );
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows[0],
+            [
+                "1",
+                "101",
+                "Jane Doe",
+                "F",
+                "34",
+                "Albuquerque",
+                "NM",
+                "1:23:45",
+                "1:24:02",
+                "6:24 /mi"
+            ]
+        );
+        let names = Placement::from_rows(&headings, &rows)
+            .into_iter()
+            .map(|placement| placement.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Jane Doe", "John Roe", "Sam Poe"]);
+    }
+
+    fn result_sets() -> Vec<(String, String)> {
+        source_options(PAGE, RESULT_SET_ID)
+    }
+
+    #[test]
+    fn reads_the_result_sets() {
+        let result_sets = result_sets();
+        assert_eq!(result_sets.len(), 4);
+        assert_eq!(
+            result_sets[3],
+            (
+                "355555".to_string(),
+                "2021 Half Marathon & Relay Results".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn a_years_only_result_set_is_chosen() {
+        let id = choose_result_set(result_sets(), Year(2022), None).unwrap();
+        assert_eq!(id, "387654");
+    }
+
+    #[test]
+    fn the_race_chooses_among_a_years_result_sets() {
+        let err = choose_result_set(result_sets(), Year(2023), None).unwrap_err();
+        assert!(err.to_string().starts_with("Choose a race"));
+        let id = choose_result_set(result_sets(), Year(2023), Some(&Race::Half)).unwrap();
+        assert_eq!(id, "412345");
+    }
+
+    #[test]
+    fn a_year_without_results_is_an_error() {
+        assert!(choose_result_set(result_sets(), Year(2020), None).is_err());
+    }
+}
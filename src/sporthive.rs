@@ -0,0 +1,203 @@
+// Sporthive's results pages are rendered from its event results API,
+// which is what's scraped here, a page of classifications at a time.
+
+use {
+    crate::{
+        paginator::Paginator,
+        placement::{Placement, Split, Status},
+        progress,
+        retry::Retry,
+        times::Times,
+        ClientExt, Event, Opt, Race, Scraper,
+    },
+    anyhow::{anyhow, bail, Result as AResult},
+    async_trait::async_trait,
+    digital_duration_nom::duration::Duration,
+    fantoccini::Client,
+    serde::Deserialize,
+    std::num::NonZeroU16,
+    tracing::{instrument, warn},
+};
+
+const API: &str = "https://eventresults-api.sporthive.com/api";
+const PER_PAGE: usize = 100;
+
+pub struct Params {
+    event_id: u64,
+    race: Option<Race>,
+    paginator: Paginator,
+    retry: Retry,
+}
+
+impl Params {
+    // Sporthive event ids are per year, so the year isn't used.
+    pub(crate) fn new(opt: Opt) -> AResult<Self> {
+        use Event::*;
+
+        match opt.event {
+            Sporthive => Ok(Self {
+                event_id: opt.id()?,
+                paginator: Paginator::unclicked(&opt),
+                retry: Retry::new(&opt),
+                race: opt.race,
+            }),
+            _ => bail!("{:?} is not Sporthive", opt.event),
+        }
+    }
+
+    #[instrument(skip_all, fields(race = ?self.race))]
+    async fn race_id(&self, client: &Client) -> AResult<u64> {
+        let url = format!("{API}/events/{}", self.event_id);
+        let event = self.retry.run(client, || client.fetch_json(&url)).await?;
+        let races = serde_json::from_value::<EventInfo>(event)?.event.races;
+        let names = races.iter().map(|r| r.name.as_str()).collect::<Vec<_>>();
+        let index = match &self.race {
+            None if races.len() == 1 => 0,
+            None => bail!("Choose a race with --race; the event has {names:?}"),
+            Some(race) => race.choose(&names, &[])?,
+        };
+        Ok(races[index].id)
+    }
+
+    fn classifications_url(&self, race_id: u64, offset: usize) -> String {
+        format!(
+            "{API}/events/{}/races/{race_id}/classifications/search?count={PER_PAGE}&offset={offset}",
+            self.event_id
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EventInfo {
+    event: EventRaces,
+}
+
+#[derive(Debug, Deserialize)]
+struct EventRaces {
+    #[serde(default)]
+    races: Vec<RaceInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RaceInfo {
+    id: u64,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Classifications {
+    #[serde(default)]
+    full_classifications: Vec<FullClassification>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FullClassification {
+    classification: Classification,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Classification {
+    rank: Option<u16>,
+    name: String,
+    bib: Option<String>,
+    gender: Option<String>,
+    category: Option<String>,
+    category_rank: Option<u16>,
+    chip_time: Option<String>,
+    gun_time: Option<String>,
+    #[serde(default)]
+    splits: Vec<SplitTime>,
+}
+
+// Splits are cumulative from the start.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SplitTime {
+    name: String,
+    cumulative_time: Option<String>,
+}
+
+fn duration(time: Option<&str>) -> Option<Duration> {
+    time?.parse().ok()
+}
+
+impl TryFrom<Classification> for Placement {
+    type Error = anyhow::Error;
+
+    fn try_from(c: Classification) -> AResult<Self> {
+        let times = Times::new(
+            None,
+            duration(c.chip_time.as_deref()),
+            duration(c.gun_time.as_deref()),
+        )
+        .ok_or_else(|| anyhow!("no time for {}", c.name))?;
+        let splits = c
+            .splits
+            .into_iter()
+            .filter_map(|split| {
+                duration(split.cumulative_time.as_deref()).map(|time| Split {
+                    split: split.name,
+                    time,
+                })
+            })
+            .collect();
+        Ok(Self {
+            rank: c.rank.and_then(NonZeroU16::new),
+            name: c.name,
+            bib: c.bib,
+            gender: c.gender,
+            age: None,
+            division: c.category,
+            division_rank: c.category_rank.and_then(NonZeroU16::new),
+            hometown: None,
+            times,
+            status: Status::Finished,
+            pace: None,
+            splits,
+        })
+    }
+}
+
+fn placements(classifications: Vec<FullClassification>) -> Vec<Placement> {
+    classifications
+        .into_iter()
+        .filter_map(|full| {
+            Placement::try_from(full.classification)
+                .inspect_err(|e| {
+                    progress::discarded();
+                    warn!(error = ?e, "discarding classification")
+                })
+                .ok()
+        })
+        .collect()
+}
+
+#[async_trait]
+impl Scraper for Params {
+    fn url(&self) -> String {
+        format!("https://results.sporthive.com/events/{}", self.event_id)
+    }
+
+    async fn doit(&self, client: &Client) -> AResult<()> {
+        let race_id = self.race_id(client).await?;
+        self.paginator
+            .paginate_numbered(client, |page| async move {
+                let offset = usize::from(page - 1) * PER_PAGE;
+                let json = client
+                    .fetch_json(&self.classifications_url(race_id, offset))
+                    .await?;
+                let seen = json.to_string();
+                let classifications = serde_json::from_value::<Classifications>(json)?;
+                if classifications.full_classifications.is_empty() {
+                    return Ok(None);
+                }
+                Ok(Some((
+                    placements(classifications.full_classifications),
+                    seen,
+                )))
+            })
+            .await
+    }
+}
@@ -0,0 +1,136 @@
+// A fallback for sites we have no scraper for that post their results as
+// a plain table.  Each row is read as a Placement, either by the table's
+// own headings or by a mapping of column numbers to Placement fields.
+
+use {
+    crate::{
+        paginator::{NextPage, Paginator},
+        placement::Placement,
+        row::headings_and_rows,
+        ClientExt, Opt, Scraper,
+    },
+    anyhow::{anyhow, bail, Result as AResult},
+    async_trait::async_trait,
+    fantoccini::{Client, Locator::Css},
+    std::str::FromStr,
+    url::Url,
+};
+
+pub struct Params {
+    url: Url,
+    table: &'static str,
+    columns: Option<Columns>,
+    paginator: Paginator,
+}
+
+impl Params {
+    // The selectors are needed for as long as the program runs, and the
+    // paginator only takes 'static ones, so they're leaked.  A next link
+    // is clicked like any other next page control; one that leads back to
+    // the page it's on is the last page's.
+    pub(crate) fn new(opt: Opt) -> AResult<Self> {
+        let table: &'static str = opt
+            .table
+            .clone()
+            .ok_or_else(|| anyhow!("Table needs a --table selector"))?
+            .leak();
+        let paginator = match opt.next.clone() {
+            None => Paginator::unclicked(&opt),
+            Some(next) => Paginator::new(&opt, NextPage::UnlessMissing(next.leak()), table),
+        };
+        Ok(Self {
+            url: opt
+                .url
+                .ok_or_else(|| anyhow!("--table needs the page's --url"))?,
+            table,
+            columns: opt.columns,
+            paginator,
+        })
+    }
+
+    async fn placements(&self, c: &Client) -> AResult<Vec<Placement>> {
+        let table = c.wait_for(Css(self.table)).await?;
+        let (mut headings, mut rows) = headings_and_rows(&table).await?;
+        if let Some(columns) = &self.columns {
+            headings = columns.headings();
+        }
+        rows.retain(|cells| !cells.is_empty());
+        Ok(Placement::from_rows(&headings, &rows))
+    }
+}
+
+/// Which fields of a Placement are in which columns, e.g.
+/// "1=rank,2=name,5=time".  Columns are numbered from 1.
+#[derive(Clone, Debug)]
+pub(crate) struct Columns(Vec<(usize, &'static str)>);
+
+// The fields a column can be mapped to, along with a heading that
+// Placement::from_row reads that field from.
+const FIELDS: [(&str, &str); 12] = [
+    ("rank", "Place"),
+    ("name", "Name"),
+    ("bib", "Bib"),
+    ("gender", "Gender"),
+    ("age", "Age"),
+    ("division", "Division"),
+    ("division_rank", "Division Place"),
+    ("hometown", "Hometown"),
+    ("time", "Time"),
+    ("chip_time", "Chip Time"),
+    ("gun_time", "Gun Time"),
+    ("pace", "Pace"),
+];
+
+impl Columns {
+    // Columns that aren't mapped get a blank heading, so they're ignored.
+    fn headings(&self) -> Vec<String> {
+        let width = self.0.iter().map(|&(i, _)| i).max().unwrap_or(0);
+        let mut headings = vec![String::new(); width];
+        for &(i, heading) in &self.0 {
+            headings[i - 1] = heading.to_string();
+        }
+        headings
+    }
+}
+
+impl FromStr for Columns {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut columns = Vec::new();
+        for mapping in s.split(',') {
+            let (column, field) = mapping
+                .split_once('=')
+                .ok_or_else(|| anyhow!("{mapping:?} isn't column=field"))?;
+            let column = match column.trim().parse::<usize>() {
+                Ok(column) if column > 0 => column,
+                _ => bail!("{column:?} isn't a column number (the first is 1)"),
+            };
+            let heading = FIELDS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(field.trim()))
+                .map(|&(_, heading)| heading)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "{field:?} isn't one of {:?}",
+                        FIELDS.iter().map(|(name, _)| name).collect::<Vec<_>>()
+                    )
+                })?;
+            columns.push((column, heading));
+        }
+        Ok(Self(columns))
+    }
+}
+
+#[async_trait]
+impl Scraper for Params {
+    fn url(&self) -> String {
+        self.url.to_string()
+    }
+
+    async fn doit(&self, client: &Client) -> AResult<()> {
+        self.paginator
+            .paginate(client, || self.placements(client))
+            .await
+    }
+}
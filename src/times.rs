@@ -0,0 +1,69 @@
+use {digital_duration_nom::duration::Duration, serde::Serialize};
+
+/// Which clock a finish time was read from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Clock {
+    Chip,
+    Gun,
+    /// The page shows a single time without saying which it is.
+    Unknown,
+}
+
+/// A finisher's times.  `time` is the time the results page ranks by and
+/// `time_is` says which clock it came from.  `chip_time` and `gun_time`
+/// are filled in whenever the page shows them, so age-group awards that
+/// use gun time can be computed even when the page ranks by chip time.
+/// `time` is only None for those listed without a time, e.g. DNFs, so that
+/// they aren't mistaken for 0:00 finishes.
+#[derive(Debug, Serialize)]
+pub(crate) struct Times {
+    pub time: Option<Duration>,
+    pub time_is: Clock,
+    pub chip_time: Option<Duration>,
+    pub gun_time: Option<Duration>,
+}
+
+impl Times {
+    /// `time` is the page's unlabeled (or official) time, if it has one.
+    /// When it doesn't, chip time is preferred over gun time, since that's
+    /// what every platform we scrape ranks by when it has both.
+    pub(crate) fn new(
+        time: Option<Duration>,
+        chip_time: Option<Duration>,
+        gun_time: Option<Duration>,
+    ) -> Option<Self> {
+        use Clock::*;
+
+        let (time, time_is) = match (time, chip_time, gun_time) {
+            (Some(time), chip, gun) => {
+                let time_is = if chip == Some(time) {
+                    Chip
+                } else if gun == Some(time) {
+                    Gun
+                } else {
+                    Unknown
+                };
+                (time, time_is)
+            }
+            (None, Some(chip), _) => (chip, Chip),
+            (None, None, Some(gun)) => (gun, Gun),
+            (None, None, None) => return None,
+        };
+        Some(Self {
+            time: Some(time),
+            time_is,
+            chip_time,
+            gun_time,
+        })
+    }
+
+    pub(crate) fn unknown(time: Option<Duration>) -> Self {
+        Self {
+            time,
+            time_is: Clock::Unknown,
+            chip_time: None,
+            gun_time: None,
+        }
+    }
+}
@@ -1,9 +1,20 @@
 use {
-    crate::{Event, Opt, Scraper},
-    anyhow::{bail, Result as AResult},
+    crate::{
+        paginator::Marker,
+        retry::Retry,
+        row::{headings_and_rows, texts, Row},
+        times::Times,
+        ClientExt, Event, Opt, Race, Scraper,
+    },
+    anyhow::{anyhow, bail, Result as AResult},
     async_trait::async_trait,
     digital_duration_nom::duration::Duration,
-    fantoccini::{elements::Element, Client, Locator::Css},
+    fantoccini::{
+        elements::Element,
+        error::{CmdError, ErrorStatus::NoSuchElement, WebDriver},
+        Client,
+        Locator::{self, Css, LinkText, XPath},
+    },
     futures::{
         pin_mut,
         stream::{self, StreamExt},
@@ -12,61 +23,118 @@ use {
     serde::Serialize,
     std::{
         convert::TryInto,
+        future::Future,
         num::{NonZeroU8, ParseIntError},
         str::FromStr,
     },
+    tracing::{instrument, warn},
+    url::Url,
 };
 
 pub struct Params {
     did: u32,
     year: String,
-    race: Option<String>,
+    race: Option<Race>,
+    // With no race given, the events we know use the distance the page
+    // shows first, but an arbitrary did has to have a single distance.
+    known_event: bool,
+    participant: bool,
+    histories: bool,
+    retry: Retry,
 }
 
 impl Params {
     pub(crate) fn new(opt: Opt) -> AResult<Self> {
         use Event::*;
 
+        let year = opt.year();
+        let retry = Retry::new(&opt);
         let mut race = None;
         let did = match opt.event {
             Moab240 => 72701,
             JJ100 => {
-                if matches!(opt.year.0, 2013 | 2018) {
-                    race = Some("100 Miler".to_string());
+                if matches!(year.0, 2013 | 2018) {
+                    race = Some(Race::Label("100 Miler".to_string()));
                 }
                 74613
             }
             DPTR => {
-                match opt.year.0 {
-                    2013 => race = Some("50 Miler".to_string()),
-                    2020 => race = Some("53 Miler".to_string()),
+                match year.0 {
+                    2013 => race = Some(Race::Label("50 Miler".to_string())),
+                    2020 => race = Some(Race::Label("53 Miler".to_string())),
                     _ => {}
                 }
                 74837
             }
             BosqueBigfoot => {
-                race = Some("50K".to_string());
+                race = Some(Race::Label("50K".to_string()));
                 67798
             }
+            UltraSignup => opt.id()?,
             e => bail!("{:?} not ultrasignup", e),
         };
+        if opt.race.is_some() {
+            race = opt.race;
+        }
         Ok(Self {
             did,
-            year: opt.year.to_string(),
+            year: year.to_string(),
             race,
+            known_event: !matches!(opt.event, UltraSignup),
+            participant: opt.participant,
+            histories: opt.histories,
+            retry,
         })
     }
 
-    async fn optionally_click_on_race(&self, client: &Client) -> AResult<()> {
-        match &self.race {
-            None => Ok(()),
-            Some(race) => self.find_and_click("a.event_link", race, client).await,
+    // Returns whether a distance's link was clicked.
+    #[instrument(skip_all, fields(race = ?self.race))]
+    async fn optionally_click_on_race(&self, client: &Client) -> AResult<bool> {
+        const CSS: &str = "a.event_link";
+
+        // Events with a single distance may not have any links at all.
+        let labels = texts(client.find_all(Css(CSS)).await?).await?;
+        let index = match (&self.race, labels.len()) {
+            (Some(race), _) => race.choose(&labels, &[])?,
+            (None, 0) => return Ok(false),
+            (None, 1) => 0,
+            (None, _) if self.known_event => return Ok(false),
+            (None, _) => bail!("Choose one of these distances with --race: {labels:?}"),
+        };
+        self.find_and_click(Css(CSS), &labels[index], client)
+            .await?;
+        Ok(true)
+    }
+
+    // Clicking a year's or a distance's link replaces the grid, and the
+    // waitlist's replaces the table of entrants, which mustn't be read
+    // until it has been.  `click` returns whether it clicked anything,
+    // which this returns too.  There's nothing to wait for when there
+    // wasn't a table before the click.
+    async fn click_replacing<F, Fut>(&self, client: &Client, css: &str, click: F) -> AResult<bool>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = AResult<bool>>,
+    {
+        let table = Marker::find(client, css).await?;
+        let clicked = self.retry.run(client, click).await?;
+        if let (true, Some(table)) = (clicked, table) {
+            if !table.replaced(client).await? {
+                warn!(css, "didn't change after clicking");
+            }
         }
+        Ok(clicked)
     }
 
-    async fn find_and_click(&self, css: &str, value: &str, client: &Client) -> AResult<()> {
+    #[instrument(skip(self, client))]
+    async fn find_and_click(
+        &self,
+        locator: Locator<'_>,
+        value: &str,
+        client: &Client,
+    ) -> AResult<()> {
         let link = client
-            .find_all(Css(css))
+            .find_all(locator)
             .map_err(Into::<anyhow::Error>::into)
             .and_then(|v| async move {
                 let stream = stream::iter(v.into_iter()).filter_map(|e| async move {
@@ -104,8 +172,292 @@ struct Placement {
     age: NonZeroU8,
     gender: String,
     gp: u16,
-    time: Duration,
+    #[serde(flatten)]
+    times: Times,
     rank: f32,
+    status: Status,
+}
+
+/// Someone registered for an upcoming race.  The fields that are also in
+/// Placement have the same names, so entrants and results can be read by
+/// the same code.
+#[derive(Debug, Serialize)]
+struct Entrant {
+    first: String,
+    last: String,
+    city: Option<String>,
+    state: Option<String>,
+    age: Option<NonZeroU8>,
+    gender: String,
+    rank: Option<f32>,
+    entry_date: Option<String>,
+    waitlist_position: Option<u16>,
+}
+
+const ENTRANTS_CSS: &str = "table.ultra_grid";
+const GRID_CSS: &str = "table#list";
+
+impl Entrant {
+    // `row_number` is the row's on the waitlist, and is None for those
+    // who aren't waitlisted.  It's only the position when the table
+    // doesn't have a position column of its own.
+    fn new(row: Row, row_number: Option<u16>) -> AResult<Self> {
+        Ok(Self {
+            first: row.required(&["First"])?,
+            last: row.required(&["Last"])?,
+            city: row.optional(&["City"]),
+            state: row.optional(&["State"]),
+            age: row.parsed(&["Age"]),
+            gender: row.required(&["Gender", "Sex"])?,
+            rank: rank(&row),
+            entry_date: row.optional(&["Registered", "Signed Up", "Entry Date"]),
+            waitlist_position: row_number
+                .map(|number| row.parsed(&["Position", "#"]).unwrap_or(number)),
+        })
+    }
+}
+
+/// One of a runner's past results, from their UltraSignup history.
+#[derive(Debug, Serialize)]
+struct PastResult {
+    date: Option<String>,
+    event: String,
+    distance: Option<String>,
+    place: Option<u16>,
+    time: Option<Duration>,
+    rank: Option<f32>,
+}
+
+impl PastResult {
+    fn new(row: Row) -> AResult<Self> {
+        Ok(Self {
+            date: row.optional(&["Date"]),
+            event: row.required(&["Event", "Race"])?,
+            distance: row.optional(&["Distance"]),
+            place: row.parsed(&["Place", "Overall", "Overall Place"]),
+            time: row.parsed(&["Time"]),
+            rank: rank(&row),
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct RunnerHistory {
+    first: String,
+    last: String,
+    results: Vec<PastResult>,
+}
+
+// UltraSignup ranks are percentages, but aren't always shown with a %.
+fn rank(row: &Row) -> Option<f32> {
+    row.get(&["Rank"])?.trim_end_matches('%').parse().ok()
+}
+
+#[instrument(skip(client))]
+async fn entrants(client: &Client, waitlisted: bool) -> AResult<Vec<Entrant>> {
+    let table = client.wait_for(Css(ENTRANTS_CSS)).await?;
+    let (headings, rows) = headings_and_rows(&table).await?;
+    let mut entrants = Vec::new();
+    // The position is the row's on the page, whether or not the rows
+    // before it could be read.
+    for (i, cells) in rows.into_iter().enumerate() {
+        let position = waitlisted.then_some(i as u16 + 1);
+        let row = Row {
+            headings: &headings,
+            cells: &cells,
+        };
+        match Entrant::new(row, position) {
+            Ok(entrant) => entrants.push(entrant),
+            Err(e) => warn!(?cells, error = ?e, "discarding row"),
+        }
+    }
+    Ok(entrants)
+}
+
+// A runner's history has a table per runner with that name, so the tables
+// are found by their having a Time column.
+async fn history(client: &Client) -> AResult<Vec<PastResult>> {
+    client.wait_for(Css("table")).await?;
+    let mut results = Vec::new();
+    for table in client.find_all(Css("table")).await? {
+        let (headings, rows) = headings_and_rows(&table).await?;
+        if !headings
+            .iter()
+            .any(|h| h.trim().eq_ignore_ascii_case("Time"))
+        {
+            continue;
+        }
+        for cells in rows {
+            let row = Row {
+                headings: &headings,
+                cells: &cells,
+            };
+            match PastResult::new(row) {
+                Ok(result) => results.push(result),
+                Err(e) => warn!(?cells, error = ?e, "discarding row"),
+            }
+        }
+    }
+    Ok(results)
+}
+
+// Only events that have filled up have a waitlist.  Returns whether there
+// was one to click on.
+async fn click_waitlist(client: &Client) -> AResult<bool> {
+    match client.find(LinkText("Waitlist")).await {
+        Ok(link) => {
+            link.click().await?;
+            Ok(true)
+        }
+        Err(CmdError::Standard(WebDriver {
+            error: NoSuchElement,
+            ..
+        })) => Ok(false),
+        Err(err) => bail!(err),
+    }
+}
+
+// The grid is filled in after the page loads, and a row with all of its
+// cells is a placement.
+async fn wait_for_grid(client: &Client) -> AResult<()> {
+    client
+        .wait_for(XPath("//table[@id='list']//tr[count(td) >= 11]"))
+        .await?;
+    Ok(())
+}
+
+// XPath has no escapes, so a name with both kinds of quote in it has to be
+// pieced together.
+fn xpath_literal(s: &str) -> String {
+    if !s.contains('\'') {
+        format!("'{s}'")
+    } else if !s.contains('"') {
+        format!("\"{s}\"")
+    } else {
+        format!(
+            "concat('{}')",
+            s.split('\'').collect::<Vec<_>>().join("', \"'\", '")
+        )
+    }
+}
+
+fn history_url(first: &str, last: &str) -> String {
+    Url::parse_with_params(
+        "https://ultrasignup.com/results_participant.aspx",
+        &[("fname", first), ("lname", last)],
+    )
+    .unwrap()
+    .to_string()
+}
+
+/// The results history of a single runner, given by name.
+pub struct History {
+    first: String,
+    last: String,
+    retry: Retry,
+}
+
+impl History {
+    pub(crate) fn new(opt: Opt) -> AResult<Self> {
+        let retry = Retry::new(&opt);
+        let runner = opt
+            .runner
+            .ok_or_else(|| anyhow!("History needs a --runner"))?;
+        let (first, last) = match runner.split_once(',') {
+            Some((last, first)) => (first, last),
+            None => runner
+                .trim()
+                .rsplit_once(' ')
+                .ok_or_else(|| anyhow!("{runner:?} needs a first and last name"))?,
+        };
+        Ok(Self {
+            first: first.trim().to_string(),
+            last: last.trim().to_string(),
+            retry,
+        })
+    }
+}
+
+#[async_trait]
+impl Scraper for History {
+    fn url(&self) -> String {
+        history_url(&self.first, &self.last)
+    }
+
+    async fn doit(&self, client: &Client) -> AResult<()> {
+        let history = RunnerHistory {
+            first: self.first.clone(),
+            last: self.last.clone(),
+            results: self.retry.run(client, || history(client)).await?,
+        };
+        println!("{}", serde_json::to_string(&history).unwrap());
+        Ok(())
+    }
+}
+
+impl Params {
+    // Follows each finisher's link to their history page in turn and comes
+    // back to the results, so this is slow.
+    #[instrument(skip_all)]
+    async fn print_histories(&self, client: &Client, placements: &Placements) -> AResult<()> {
+        let mut histories = Vec::new();
+        for placement in &placements.placements {
+            // The links in the finisher's row, of which the first name is
+            // the one to their history.
+            let links = format!(
+                "//table[@id='list']//tr[normalize-space(td[3])={} and normalize-space(td[4])={}]//a",
+                xpath_literal(&placement.first),
+                xpath_literal(&placement.last)
+            );
+            let results = self
+                .retry
+                .run(client, || async {
+                    wait_for_grid(client).await?;
+                    self.find_and_click(XPath(&links), &placement.first, client)
+                        .await?;
+                    let results = history(client).await;
+                    client.back().await?;
+                    results
+                })
+                .await?;
+            histories.push(RunnerHistory {
+                first: placement.first.clone(),
+                last: placement.last.clone(),
+                results,
+            });
+        }
+        println!("{}", serde_json::to_string(&histories).unwrap());
+        Ok(())
+    }
+
+    async fn print_entrants(&self, client: &Client) -> AResult<()> {
+        let retry = self.retry;
+        // The entrants are only there once their race and the Entrants link
+        // have been clicked, so when reading them fails, the clicks are
+        // done again on the page freshly loaded.
+        let mut reload = false;
+        let mut all = retry
+            .run(client, || {
+                let reload = std::mem::replace(&mut reload, true);
+                async move {
+                    if reload {
+                        client.goto(&self.url()).await?;
+                    }
+                    self.optionally_click_on_race(client).await?;
+                    client.find(LinkText("Entrants")).await?.click().await?;
+                    entrants(client, false).await
+                }
+            })
+            .await?;
+        if self
+            .click_replacing(client, ENTRANTS_CSS, || click_waitlist(client))
+            .await?
+        {
+            all.extend(retry.run(client, || entrants(client, true)).await?);
+        }
+        println!("{}", serde_json::to_string(&all).unwrap());
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -115,13 +467,21 @@ impl Scraper for Params {
     }
 
     async fn doit(&self, client: &Client) -> AResult<()> {
-        self.find_and_click("a.year_link", &self.year, client)
+        if self.participant {
+            return self.print_entrants(client).await;
+        }
+        let retry = self.retry;
+        self.click_replacing(client, GRID_CSS, || {
+            self.find_and_click(Css("a.year_link"), &self.year, client)
+                .map_ok(|()| true)
+        })
+        .await?;
+        self.click_replacing(client, GRID_CSS, || self.optionally_click_on_race(client))
             .await?;
-        self.optionally_click_on_race(client).await?;
 
-        tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+        retry.run(client, || wait_for_grid(client)).await?;
 
-        let placements_or_statuses = client
+        let placements = client
             .find_all(Css("table#list tbody tr"))
             .map_err(|e| e.into())
             .and_then(|v| async move {
@@ -129,20 +489,21 @@ impl Scraper for Params {
                     .filter_map(
                         |e| async move { PlacementOrStatus::from_element(e).await.transpose() },
                     )
-                    .try_collect::<StatusesWithPlacements>()
+                    .try_collect::<Placements>()
                     .await
             })
             .await?;
-        println!(
-            "{}",
-            serde_json::to_string(&placements_or_statuses).unwrap()
-        );
+        println!("{}", serde_json::to_string(&placements.placements).unwrap());
+        if self.histories {
+            self.print_histories(client, &placements).await?;
+        }
         Ok(())
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Clone, Copy, Debug, Default, Serialize)]
 enum Status {
+    #[default]
     Finishers = 1,
     DidNotFinish = 2,
     DidNotStart = 3,
@@ -233,16 +594,17 @@ impl PlacementOrStatus {
                 match v[1].parse() {
                     Err(_) => Ok(None),
                     Ok(place) => {
+                        // Those who didn't finish have no time.
                         let time = if v[9].trim().is_empty() {
-                            Duration::new(0, 0)
+                            None
                         } else {
-                            v[9].parse::<Duration>().or_else(|e| {
+                            Some(v[9].parse::<Duration>().or_else(|e| {
                                 if v[9] == "101:46:3" {
                                     "101:46:03".parse()
                                 } else {
                                     Err(e)
                                 }
-                            })?
+                            })?)
                         };
                         let city = optional_string(&v[4]);
                         let state = optional_string(&v[5]);
@@ -258,8 +620,11 @@ impl PlacementOrStatus {
                             age,
                             gender: v[7].to_string(),
                             gp,
-                            time,
+                            times: Times::unknown(time),
                             rank,
+                            // Filled in from the preceding status row
+                            // when collected into Placements.
+                            status: Status::default(),
                         })))
                     }
                 }
@@ -276,17 +641,26 @@ fn optional_string(s: &str) -> Option<String> {
     }
 }
 
-#[derive(Debug, Default, Serialize)]
-struct StatusesWithPlacements(Vec<(StatusWithCount, Vec<Placement>)>);
+/// Every placement, each with the status of the group it's listed under.
+/// Placements that precede every status row are taken to be finishers,
+/// since that's the group UltraSignup lists first.
+#[derive(Debug, Default)]
+struct Placements {
+    status: Status,
+    placements: Vec<Placement>,
+}
 
-impl Extend<PlacementOrStatus> for StatusesWithPlacements {
+impl Extend<PlacementOrStatus> for Placements {
     fn extend<T: IntoIterator<Item = PlacementOrStatus>>(&mut self, iter: T) {
         use PlacementOrStatus::*;
 
         for elem in iter {
             match elem {
-                Placement(p) => self.0.last_mut().expect("no status").1.push(p),
-                StatusWithCount(s) => self.0.push((s, Vec::new())),
+                Placement(mut p) => {
+                    p.status = self.status;
+                    self.placements.push(p);
+                }
+                StatusWithCount(s) => self.status = s.status,
             }
         }
     }
@@ -0,0 +1,115 @@
+use {
+    crate::{
+        paginator::Marker,
+        placement::{Placement, TeamPlacement},
+        retry::Retry,
+        row::{headings_and_rows, texts},
+        ClientExt, Event, Opt, Race, Scraper,
+    },
+    anyhow::{anyhow, bail, Result as AResult},
+    async_trait::async_trait,
+    fantoccini::{Client, Locator::Css},
+    tracing::{instrument, warn},
+};
+
+const CATEGORY_SELECTOR: &str = "select#CategoryList";
+const RESULTS_CSS: &str = "table.results-table";
+
+pub struct Params {
+    race_id: u32,
+    category: Option<Race>,
+    retry: Retry,
+}
+
+impl Params {
+    // Webscorer race ids are per year, so the year isn't used.  The race
+    // option chooses a category, since that's how Webscorer divides up a
+    // race's results (e.g. "10K", "Men 40-49").
+    pub(crate) fn new(opt: Opt) -> AResult<Self> {
+        use Event::*;
+
+        match opt.event {
+            Webscorer => Ok(Self {
+                race_id: opt.id()?,
+                retry: Retry::new(&opt),
+                category: opt.race,
+            }),
+            _ => bail!("{:?} is not Webscorer", opt.event),
+        }
+    }
+
+    // Choosing a category replaces the results tables with the category's,
+    // which mustn't be read until they have been.
+    #[instrument(skip_all, fields(category = ?self.category))]
+    async fn select_category(&self, c: &Client) -> AResult<()> {
+        let category = match &self.category {
+            None => return Ok(()),
+            Some(category) => category,
+        };
+        c.wait_for(Css(RESULTS_CSS)).await?;
+        let results = Marker::find(c, RESULTS_CSS).await?;
+        self.retry.run(c, || choose_category(c, category)).await?;
+        if let Some(results) = results {
+            if !results.replaced(c).await? {
+                warn!(
+                    css = RESULTS_CSS,
+                    "didn't change after choosing the category"
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn choose_category(c: &Client, category: &Race) -> AResult<()> {
+    let select = c.wait_for(Css(CATEGORY_SELECTOR)).await?;
+    let labels = texts(select.find_all(Css("option")).await?).await?;
+    let index = category
+        .choose(&labels, &[])
+        .map_err(|e| anyhow!("{CATEGORY_SELECTOR}: {e}"))?;
+    select.select_by_label(&labels[index]).await?;
+    Ok(())
+}
+
+// A team results table has the team's name where an individual results
+// table has the runner's.  Individual tables can have a team column too.
+fn is_team_table(headings: &[String]) -> bool {
+    let has = |names: &[&str]| {
+        headings
+            .iter()
+            .any(|h| names.iter().any(|n| h.trim().eq_ignore_ascii_case(n)))
+    };
+    has(&["Team", "Team Name"]) && !has(&["Name", "Participant", "Athlete"])
+}
+
+#[async_trait]
+impl Scraper for Params {
+    fn url(&self) -> String {
+        format!("https://www.webscorer.com/race?raceid={}", self.race_id)
+    }
+
+    // All of a race's results are on one page, although they may be in
+    // more than one table (e.g. one per category, with the teams last).
+    async fn doit(&self, client: &Client) -> AResult<()> {
+        let retry = self.retry;
+        self.select_category(client).await?;
+        retry
+            .run(client, || client.wait_for(Css(RESULTS_CSS)))
+            .await?;
+        let mut placements = Vec::new();
+        let mut team_placements = Vec::new();
+        for table in client.find_all(Css(RESULTS_CSS)).await? {
+            let (headings, rows) = headings_and_rows(&table).await?;
+            if is_team_table(&headings) {
+                team_placements.extend(TeamPlacement::from_rows(&headings, &rows));
+            } else {
+                placements.extend(Placement::from_rows(&headings, &rows));
+            }
+        }
+        println!("{}", serde_json::to_string(&placements).unwrap());
+        if !team_placements.is_empty() {
+            println!("{}", serde_json::to_string(&team_placements).unwrap());
+        }
+        Ok(())
+    }
+}